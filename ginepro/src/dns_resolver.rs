@@ -4,6 +4,10 @@ use crate::{LookupService, ServiceDefinition};
 use anyhow::Context;
 use std::collections::HashSet;
 use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::time::Duration;
+use trust_dns_resolver::lookup_ip::LookupIp;
 use trust_dns_resolver::{system_conf, AsyncResolver, TokioAsyncResolver};
 
 /// Implements [`LookupService`] by using DNS queries to lookup [`ServiceDefinition::hostname`].
@@ -11,6 +15,18 @@ pub struct DnsResolver {
     /// The trust-dns resolver which contacts the dns service directly such
     /// that we bypass os-specific dns caching.
     dns: TokioAsyncResolver,
+    /// Whether every DNS query and response is logged at `TRACE` level.
+    #[cfg(feature = "trace-dns")]
+    packet_logging: bool,
+    /// How long DNS queries may take, if limited on top of the system configuration.
+    timeout_backoff: Option<TimeoutBackoff>,
+}
+
+/// A DNS query timeout that doubles on every consecutive timeout.
+struct TimeoutBackoff {
+    initial: Duration,
+    max: Duration,
+    current: Mutex<Duration>,
 }
 
 impl DnsResolver {
@@ -24,29 +40,132 @@ impl DnsResolver {
 
         let dns = AsyncResolver::tokio(config, opts).expect("resolver must be valid");
 
-        Ok(Self { dns })
+        Ok(Self {
+            dns,
+            #[cfg(feature = "trace-dns")]
+            packet_logging: false,
+            timeout_backoff: None,
+        })
+    }
+
+    /// Give up on DNS queries after `initial`, doubling the timeout on every consecutive
+    /// timeout up to `max` and going back to `initial` on the first query that succeeds.
+    ///
+    /// This adapts to momentarily slow DNS servers without always waiting for the full
+    /// timeout of the system configuration. That timeout still applies: a `max` beyond it
+    /// has no effect.
+    pub fn with_timeout_backoff(self, initial: Duration, max: Duration) -> Self {
+        Self {
+            timeout_backoff: Some(TimeoutBackoff {
+                initial,
+                max,
+                current: Mutex::new(initial),
+            }),
+            ..self
+        }
+    }
+
+    /// Resolve `hostname`, applying the timeout backoff if there is one.
+    async fn lookup_ip(&self, hostname: &str) -> Result<LookupIp, anyhow::Error> {
+        let backoff = match self.timeout_backoff {
+            Some(ref backoff) => backoff,
+            None => return Ok(self.dns.lookup_ip(hostname).await?),
+        };
+
+        let timeout = *backoff.current.lock().expect("dns timeout lock poisoned");
+        match tokio::time::timeout(timeout, self.dns.lookup_ip(hostname)).await {
+            Ok(lookup) => {
+                let lookup = lookup?;
+                *backoff.current.lock().expect("dns timeout lock poisoned") = backoff.initial;
+                Ok(lookup)
+            }
+            Err(_) => {
+                let mut current = backoff.current.lock().expect("dns timeout lock poisoned");
+                *current = current.saturating_mul(2).min(backoff.max);
+                Err(anyhow::anyhow!(
+                    "dns query for {} timed out after {:?}",
+                    hostname,
+                    timeout
+                ))
+            }
+        }
+    }
+
+    /// Log every DNS query and the records of its response as `TRACE` events.
+    ///
+    /// This produces a log line per record on every probe, so it is only meant for debugging.
+    #[cfg(feature = "trace-dns")]
+    pub fn with_packet_logging(self, enabled: bool) -> Self {
+        Self {
+            packet_logging: enabled,
+            ..self
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl LookupService for DnsResolver {
-    #[tracing::instrument(level = "debug", skip(self))]
     async fn resolve_service_endpoints(
         &self,
         definition: &ServiceDefinition,
     ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
-        match self.dns.lookup_ip(definition.hostname.as_ref()).await {
+        let (endpoints, _) = self
+            .resolve_service_endpoints_with_expiry(definition)
+            .await?;
+        Ok(endpoints)
+    }
+
+    /// The endpoints expire with the record that has the lowest TTL.
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn resolve_service_endpoints_with_expiry(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<(HashSet<SocketAddr>, Option<Instant>), anyhow::Error> {
+        #[cfg(feature = "trace-dns")]
+        if self.packet_logging {
+            tracing::trace!(hostname = %definition.hostname, "dns query");
+        }
+
+        match self.lookup_ip(definition.hostname.as_ref()).await {
             Ok(lookup) => {
+                #[cfg(feature = "trace-dns")]
+                if self.packet_logging {
+                    let query = lookup.query();
+                    tracing::trace!(
+                        name = %query.name(),
+                        record_type = %query.query_type(),
+                        records = lookup.as_lookup().record_iter().count(),
+                        "dns response"
+                    );
+                    for record in lookup.as_lookup().record_iter() {
+                        tracing::trace!(
+                            name = %record.name(),
+                            record_type = %record.record_type(),
+                            ttl = record.ttl(),
+                            rdata = ?record.rdata(),
+                            "dns response record"
+                        );
+                    }
+                }
+
                 tracing::debug!("dns query expires in: {:?}", lookup.valid_until());
-                Ok(lookup
+                let endpoints = lookup
                     .iter()
                     .map(|ip_addr| {
                         tracing::debug!("result: ip {}", ip_addr);
                         (ip_addr, definition.port).into()
                     })
-                    .collect())
+                    .collect();
+                Ok((endpoints, Some(lookup.valid_until())))
+            }
+            Err(err) => {
+                #[cfg(feature = "trace-dns")]
+                if self.packet_logging {
+                    tracing::trace!(hostname = %definition.hostname, error = ?err, "dns error response");
+                }
+
+                Err(err)
             }
-            Err(err) => Err(err.into()),
         }
     }
 }
@@ -0,0 +1,12 @@
+//! Events describing changes to the set of endpoints of a [`LoadBalancedChannel`](crate::LoadBalancedChannel).
+
+use std::net::SocketAddr;
+
+/// A change to the set of endpoints that has been reported to the channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndpointChangeEvent {
+    /// A new endpoint was discovered and added to the channel.
+    Added(SocketAddr),
+    /// An endpoint disappeared and was removed from the channel.
+    Removed(SocketAddr),
+}
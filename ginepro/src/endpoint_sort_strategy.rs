@@ -0,0 +1,107 @@
+//! Defines the order in which newly discovered endpoints are reported to tonic.
+
+use crate::service_probe::connection_permit;
+use rand::seq::SliceRandom;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio::time::{Duration, Instant};
+
+// The longest we wait for a TCP connection when measuring the latency of an endpoint.
+// The wait is further bounded by the probe interval, so that measuring latencies
+// cannot delay the next probe cycle by more than one interval.
+// Endpoints that do not accept a connection within this time are sorted last.
+static LATENCY_MEASUREMENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Controls the order in which newly discovered endpoints are submitted to the
+/// [`Channel`](tonic::transport::Channel).
+///
+/// Tonic may establish connections in the order endpoints are reported, which affects
+/// how load is initially distributed across them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EndpointSortStrategy {
+    /// Submit endpoints in no particular order.
+    ///
+    /// The [`LookupService`](crate::LookupService) returns a set of endpoints,
+    /// so the order is arbitrary and may change between probe cycles.
+    #[default]
+    None,
+    /// Submit endpoints sorted by their IP address and port.
+    ByIpAddress,
+    /// Submit endpoints in a random order.
+    Random,
+    /// Submit endpoints in ascending order of the time it takes to open a TCP connection to them.
+    ///
+    /// The latency of every new endpoint is measured before it is submitted,
+    /// endpoints that cannot be reached are submitted last.
+    ///
+    /// Measuring opens (and immediately closes) a real TCP connection to every new endpoint
+    /// from the probe task. The probe waits for all measurements to finish before reporting
+    /// the changes, which takes at most one second or the probe interval, whichever is shorter.
+    /// With a [connection backlog](crate::LoadBalancedChannelBuilder::with_connection_backlog)
+    /// the measurements wait for their turn, so the probe may take longer than that.
+    ByLatency,
+}
+
+impl EndpointSortStrategy {
+    /// Sort `endpoints` according to the strategy.
+    /// Latency measurements give up after `probe_interval` at the latest
+    /// and open at most as many connections at the same time as the `backlog` allows.
+    pub(crate) async fn sort(
+        &self,
+        mut endpoints: Vec<SocketAddr>,
+        probe_interval: Duration,
+        backlog: &Option<Arc<Semaphore>>,
+    ) -> Vec<SocketAddr> {
+        match self {
+            Self::None => {}
+            Self::ByIpAddress => endpoints.sort(),
+            Self::Random => endpoints.shuffle(&mut rand::thread_rng()),
+            Self::ByLatency => {
+                let timeout = LATENCY_MEASUREMENT_TIMEOUT.min(probe_interval);
+                let measurements: Vec<_> = endpoints
+                    .into_iter()
+                    .map(|addr| {
+                        let backlog = backlog.clone();
+                        let measurement = async move {
+                            let _permit = connection_permit(&backlog).await;
+                            measure_latency(addr, timeout).await
+                        };
+                        (addr, tokio::spawn(measurement))
+                    })
+                    .collect();
+
+                let mut latencies = Vec::with_capacity(measurements.len());
+                for (addr, measurement) in measurements {
+                    latencies.push((measurement.await.ok().flatten(), addr));
+                }
+
+                // `None` sorts before `Some`, so unreachable endpoints are moved to the back explicitly.
+                latencies.sort_by_key(|(latency, _)| (latency.is_none(), *latency));
+
+                endpoints = latencies.into_iter().map(|(_, addr)| addr).collect();
+            }
+        }
+
+        endpoints
+    }
+}
+
+/// Measure how long it takes to open a TCP connection to `addr`.
+/// Returns `None` if the connection failed or did not succeed within `timeout`.
+async fn measure_latency(addr: SocketAddr, timeout: Duration) -> Option<Duration> {
+    let start = Instant::now();
+
+    match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => Some(start.elapsed()),
+        Ok(Err(err)) => {
+            tracing::debug!("failed to measure latency of {}: {:?}", addr, err);
+            None
+        }
+        Err(_) => {
+            tracing::debug!("timed out measuring latency of {}", addr);
+            None
+        }
+    }
+}
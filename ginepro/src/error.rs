@@ -0,0 +1,56 @@
+//! The errors returned by `ginepro`.
+
+use std::error::Error;
+use std::fmt;
+
+/// An error returned while configuring or using a [`LoadBalancedChannel`](crate::LoadBalancedChannel).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GineproError {
+    /// The environment variable `var` is set, but not to a valid value.
+    InvalidEnvVar {
+        /// The name of the environment variable.
+        var: String,
+        /// The value of the environment variable, lossily converted to UTF-8.
+        value: String,
+        /// Why the value is not valid.
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// The channel has no endpoints to send requests to.
+    NoEndpointsFound,
+    /// Resolving the endpoints of `hostname` failed.
+    LookupFailed {
+        /// The hostname that was resolved.
+        hostname: String,
+        /// Why the lookup failed.
+        source: Box<dyn Error + Send + Sync>,
+    },
+}
+
+impl fmt::Display for GineproError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GineproError::InvalidEnvVar { var, value, .. } => {
+                write!(
+                    f,
+                    "invalid value {:?} for environment variable {}",
+                    value, var
+                )
+            }
+            GineproError::NoEndpointsFound => write!(f, "no endpoints found"),
+            GineproError::LookupFailed { hostname, .. } => {
+                write!(f, "failed to resolve the endpoints of {}", hostname)
+            }
+        }
+    }
+}
+
+impl Error for GineproError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GineproError::InvalidEnvVar { source, .. } => Some(source.as_ref()),
+            GineproError::NoEndpointsFound => None,
+            GineproError::LookupFailed { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
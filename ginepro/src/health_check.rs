@@ -0,0 +1,133 @@
+//! Implements [`LookupService`] on top of another [`LookupService`], keeping only the endpoints
+//! that pass the gRPC health check.
+
+use crate::{LookupService, ServiceDefinition};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::Instant;
+use tokio::time::Duration;
+use tonic::transport::{ClientTlsConfig, Endpoint};
+use tonic_health::proto::health_check_response::ServingStatus;
+use tonic_health::proto::health_client::HealthClient;
+use tonic_health::proto::HealthCheckRequest;
+
+// How long a health check may take before the endpoint is considered unhealthy.
+static DEFAULT_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Wraps a [`LookupService`] and filters out the endpoints that do not report `SERVING`
+/// on the `grpc.health.v1.Health/Check` RPC.
+///
+/// Every endpoint is checked concurrently on every lookup, over a new connection that is
+/// closed afterwards. Endpoints that cannot be reached, time out, or report any other status
+/// are left out.
+pub struct GrpcHealthCheckService<L> {
+    inner: L,
+    service_name: String,
+    timeout: Duration,
+    tls_config: Option<ClientTlsConfig>,
+}
+
+impl<L: LookupService> GrpcHealthCheckService<L> {
+    /// Check the health of `service_name` on every endpoint resolved by `inner`.
+    /// An empty `service_name` checks the overall health of the server.
+    pub fn new(inner: L, service_name: impl Into<String>) -> Self {
+        Self {
+            inner,
+            service_name: service_name.into(),
+            timeout: DEFAULT_HEALTH_CHECK_TIMEOUT,
+            tls_config: None,
+        }
+    }
+
+    /// Consider an endpoint unhealthy if its health check takes longer than `timeout`.
+    /// Default is one second.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Connect to the endpoints with tls.
+    /// Since endpoints are addressed by IP, the `tls_config` must set the domain name.
+    pub fn with_tls(self, tls_config: ClientTlsConfig) -> Self {
+        Self {
+            tls_config: Some(tls_config),
+            ..self
+        }
+    }
+
+    async fn is_serving(&self, endpoint: SocketAddr) -> bool {
+        match self.check(endpoint).await {
+            Ok(status) if status == ServingStatus::Serving as i32 => true,
+            Ok(status) => {
+                tracing::debug!("endpoint {} reported health status {}", endpoint, status);
+                false
+            }
+            Err(err) => {
+                tracing::debug!("health check of endpoint {} failed: {:?}", endpoint, err);
+                false
+            }
+        }
+    }
+
+    async fn check(&self, endpoint: SocketAddr) -> Result<i32, anyhow::Error> {
+        let scheme = match self.tls_config {
+            Some(_) => "https",
+            None => "http",
+        };
+        let mut endpoint = Endpoint::from_shared(format!("{}://{}", scheme, endpoint))?
+            .connect_timeout(self.timeout)
+            .timeout(self.timeout);
+        if let Some(ref tls_config) = self.tls_config {
+            endpoint = endpoint.tls_config(tls_config.clone())?;
+        }
+
+        let check = async {
+            let mut client = HealthClient::new(endpoint.connect().await?);
+            let response = client
+                .check(HealthCheckRequest {
+                    service: self.service_name.clone(),
+                })
+                .await?;
+            Ok::<_, anyhow::Error>(response.into_inner().status)
+        };
+
+        tokio::time::timeout(self.timeout, check).await?
+    }
+
+    /// Check all `endpoints` concurrently and keep the ones that are serving.
+    async fn serving(&self, endpoints: HashSet<SocketAddr>) -> HashSet<SocketAddr> {
+        let checks = endpoints.into_iter().map(|endpoint| async move {
+            match self.is_serving(endpoint).await {
+                true => Some(endpoint),
+                false => None,
+            }
+        });
+
+        futures_util::future::join_all(checks)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl<L: LookupService + Send + Sync> LookupService for GrpcHealthCheckService<L> {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let endpoints = self.inner.resolve_service_endpoints(definition).await?;
+        Ok(self.serving(endpoints).await)
+    }
+
+    async fn resolve_service_endpoints_with_expiry(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<(HashSet<SocketAddr>, Option<Instant>), anyhow::Error> {
+        let (endpoints, expires_at) = self
+            .inner
+            .resolve_service_endpoints_with_expiry(definition)
+            .await?;
+        Ok((self.serving(endpoints).await, expires_at))
+    }
+}
@@ -99,11 +99,37 @@
 
 mod balanced_channel;
 mod dns_resolver;
+mod endpoint_change;
+mod endpoint_sort_strategy;
+mod error;
+#[cfg(feature = "health-check")]
+mod health_check;
+mod lookup_retry;
 mod lookup_service;
+pub mod middleware;
+mod nat64;
+mod resolver_cache;
 mod service_definition;
 mod service_probe;
+#[cfg(feature = "opentelemetry")]
+mod telemetry;
+mod unary_service;
+#[cfg(feature = "zerotier")]
+mod zerotier;
 
 pub use balanced_channel::*;
 pub use dns_resolver::*;
+pub use endpoint_change::*;
+pub use endpoint_sort_strategy::*;
+pub use error::*;
+#[cfg(feature = "health-check")]
+pub use health_check::*;
+pub use lookup_retry::*;
 pub use lookup_service::*;
+pub use nat64::*;
+pub use resolver_cache::*;
 pub use service_definition::*;
+pub use service_probe::{GrpcServiceProbe, GrpcServiceProbeConfig, ResolveService};
+pub use unary_service::*;
+#[cfg(feature = "zerotier")]
+pub use zerotier::*;
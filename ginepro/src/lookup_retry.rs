@@ -0,0 +1,104 @@
+//! Implements [`LookupService`] on top of another [`LookupService`], bounding how long
+//! lookups take and retrying the ones that fail.
+
+use crate::{LookupService, ServiceDefinition};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::Instant;
+use tokio::time::Duration;
+
+/// Wraps a [`LookupService`] and fails lookups that take longer than `timeout`.
+pub struct TimeoutLookupService<L> {
+    inner: L,
+    timeout: Duration,
+}
+
+impl<L: LookupService> TimeoutLookupService<L> {
+    /// Fail lookups of `inner` that take longer than `timeout`.
+    pub fn new(inner: L, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+#[async_trait::async_trait]
+impl<L: LookupService + Send + Sync> LookupService for TimeoutLookupService<L> {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let (endpoints, _) = self
+            .resolve_service_endpoints_with_expiry(definition)
+            .await?;
+        Ok(endpoints)
+    }
+
+    async fn resolve_service_endpoints_with_expiry(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<(HashSet<SocketAddr>, Option<Instant>), anyhow::Error> {
+        let lookup = self.inner.resolve_service_endpoints_with_expiry(definition);
+        tokio::time::timeout(self.timeout, lookup)
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "lookup of {} timed out after {:?}",
+                    definition,
+                    self.timeout
+                )
+            })?
+    }
+}
+
+/// Wraps a [`LookupService`] and retries failed lookups right away, up to `retries` times.
+/// The error of the last attempt is returned if they all fail.
+pub struct RetryingLookupService<L> {
+    inner: L,
+    retries: u32,
+}
+
+impl<L: LookupService> RetryingLookupService<L> {
+    /// Retry failed lookups of `inner` up to `retries` times.
+    pub fn new(inner: L, retries: u32) -> Self {
+        Self { inner, retries }
+    }
+}
+
+#[async_trait::async_trait]
+impl<L: LookupService + Send + Sync> LookupService for RetryingLookupService<L> {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let (endpoints, _) = self
+            .resolve_service_endpoints_with_expiry(definition)
+            .await?;
+        Ok(endpoints)
+    }
+
+    async fn resolve_service_endpoints_with_expiry(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<(HashSet<SocketAddr>, Option<Instant>), anyhow::Error> {
+        let mut attempt = 0;
+        loop {
+            match self
+                .inner
+                .resolve_service_endpoints_with_expiry(definition)
+                .await
+            {
+                Ok(lookup) => return Ok(lookup),
+                Err(err) if attempt < self.retries => {
+                    attempt += 1;
+                    tracing::debug!(
+                        "lookup of {} failed, retrying ({}/{}): {:?}",
+                        definition,
+                        attempt,
+                        self.retries,
+                        err
+                    );
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
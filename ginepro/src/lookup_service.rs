@@ -1,7 +1,11 @@
 //! Defines the interface that [`LoadBalancedChannel`](crate::LoadBalancedChannel) requires in order
 //! to resolve all the IP adresses for a given service.
 
-use std::{collections::HashSet, net::SocketAddr};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    time::Instant,
+};
 
 use crate::ServiceDefinition;
 
@@ -16,4 +20,42 @@ pub trait LookupService {
         &self,
         definition: &ServiceDefinition,
     ) -> Result<HashSet<SocketAddr>, anyhow::Error>;
+
+    /// Like [`resolve_service_endpoints`](Self::resolve_service_endpoints), but also return
+    /// when the resolved endpoints expire, e.g. from the TTL of the DNS records.
+    /// The default implementation does not know when the endpoints expire.
+    async fn resolve_service_endpoints_with_expiry(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<(HashSet<SocketAddr>, Option<Instant>), anyhow::Error>
+    where
+        Self: Sync,
+    {
+        Ok((self.resolve_service_endpoints(definition).await?, None))
+    }
+}
+
+/// Resolve all `services` concurrently with `resolver`, e.g. to wait for every downstream
+/// service to be resolvable at startup.
+///
+/// A service whose lookup fails maps to no endpoints, the failure is logged as a warning.
+pub async fn resolve_all(
+    services: Vec<ServiceDefinition>,
+    resolver: &impl LookupService,
+) -> HashMap<ServiceDefinition, Vec<SocketAddr>> {
+    let lookups = services.into_iter().map(|service| async move {
+        let endpoints = match resolver.resolve_service_endpoints(&service).await {
+            Ok(endpoints) => endpoints.into_iter().collect(),
+            Err(err) => {
+                tracing::warn!("failed to resolve {}: {:?}", service, err);
+                Vec::new()
+            }
+        };
+        (service, endpoints)
+    });
+
+    futures_util::future::join_all(lookups)
+        .await
+        .into_iter()
+        .collect()
 }
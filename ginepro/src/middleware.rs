@@ -0,0 +1,394 @@
+//! Tower middleware that can be applied to a [`LoadBalancedChannel`](crate::LoadBalancedChannel)
+//! or to any other service speaking `http` with a tonic [`BoxBody`].
+
+use bytes::{Bytes, BytesMut};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use http::Request;
+use http_body::Body;
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::time::{Duration, Instant};
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service, ServiceExt};
+use tracing::Level;
+
+/// A [`Layer`] that wraps services in a [`HedgingService`].
+#[derive(Debug, Clone, Copy)]
+pub struct HedgingLayer {
+    delay: Duration,
+    max_hedged_requests: usize,
+}
+
+impl HedgingLayer {
+    /// Send another copy of a request every `delay` it has gone unanswered,
+    /// up to `max_hedged_requests` copies on top of the original request.
+    pub fn new(delay: Duration, max_hedged_requests: usize) -> Self {
+        Self {
+            delay,
+            max_hedged_requests,
+        }
+    }
+}
+
+impl<S> Layer<S> for HedgingLayer {
+    type Service = HedgingService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HedgingService {
+            inner,
+            layer: *self,
+        }
+    }
+}
+
+/// Speculatively retries slow requests.
+///
+/// If a request has not been answered after `delay`, a copy of it is sent through a clone of
+/// the inner service, which load balances it to a likely different endpoint. This repeats
+/// every `delay` until `max_hedged_requests` copies are in flight. The first successful
+/// response is returned and the requests still in flight are cancelled by dropping them.
+/// An error is only returned once every request has failed.
+///
+/// To be copied, the request body is buffered in full before the original request is sent.
+/// Hedging is therefore only meant for unary and server streaming calls: a client streaming
+/// call is not sent until its stream ends, and a bidirectional streaming call that waits for
+/// a response before sending more messages never completes.
+/// Request extensions are only passed on to the original request.
+#[derive(Debug, Clone)]
+pub struct HedgingService<S> {
+    inner: S,
+    layer: HedgingLayer,
+}
+
+impl<S> HedgingService<S> {
+    /// Wrap `inner`, see [`HedgingLayer::new`].
+    pub fn new(inner: S, delay: Duration, max_hedged_requests: usize) -> Self {
+        HedgingLayer::new(delay, max_hedged_requests).layer(inner)
+    }
+}
+
+pub(crate) type ResponseFuture<R, E> = Pin<Box<dyn Future<Output = Result<R, E>> + Send>>;
+
+impl<S> Service<Request<BoxBody>> for HedgingService<S>
+where
+    S: Service<Request<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Response: Send,
+    S::Error: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Response, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<BoxBody>) -> Self::Future {
+        // The service that has been polled ready sends the original request,
+        // the copies go through clones that still have to become ready.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let HedgingLayer {
+            delay,
+            max_hedged_requests,
+        } = self.layer;
+
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let (data, error) = buffer(body).await;
+
+            // A body we could not read in full is passed on once, with its error.
+            if error.is_some() || max_hedged_requests == 0 {
+                let body = ReplayBody {
+                    data: Some(data),
+                    error,
+                }
+                .boxed();
+                return inner.call(Request::from_parts(parts, body)).await;
+            }
+
+            // Copies are only made when they are sent, so that a large `max_hedged_requests`
+            // does not allocate them all up front.
+            let (method, uri, version, headers) = (
+                parts.method.clone(),
+                parts.uri.clone(),
+                parts.version,
+                parts.headers.clone(),
+            );
+            let copy = |data: &Bytes| {
+                let mut request = Request::new(ReplayBody::new(data.clone()).boxed());
+                *request.method_mut() = method.clone();
+                *request.uri_mut() = uri.clone();
+                *request.version_mut() = version;
+                *request.headers_mut() = headers.clone();
+                request
+            };
+
+            let original = Request::from_parts(parts, ReplayBody::new(data.clone()).boxed());
+
+            let mut responses: FuturesUnordered<ResponseFuture<S::Response, S::Error>> =
+                FuturesUnordered::new();
+            responses.push(Box::pin(inner.call(original)));
+
+            let mut hedges_left = max_hedged_requests;
+            let next_hedge = tokio::time::sleep(delay);
+            tokio::pin!(next_hedge);
+
+            loop {
+                tokio::select! {
+                    response = responses.next() => match response {
+                        Some(Ok(response)) => return Ok(response),
+                        Some(Err(err)) if responses.is_empty() => return Err(err),
+                        Some(Err(_)) => {}
+                        None => unreachable!("hedged requests are only awaited while in flight"),
+                    },
+                    _ = &mut next_hedge, if hedges_left > 0 => {
+                        hedges_left -= 1;
+                        let request = copy(&data);
+                        let mut service = inner.clone();
+                        responses.push(Box::pin(async move {
+                            service.ready().await?.call(request).await
+                        }));
+                        next_hedge.as_mut().reset(tokio::time::Instant::now() + delay);
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Read `body` to the end, stopping at the first error.
+async fn buffer(mut body: BoxBody) -> (Bytes, Option<Status>) {
+    let mut data = BytesMut::new();
+
+    while let Some(chunk) = body.data().await {
+        match chunk {
+            Ok(chunk) => data.extend_from_slice(&chunk),
+            Err(status) => return (data.freeze(), Some(status)),
+        }
+    }
+
+    (data.freeze(), None)
+}
+
+/// A body that yields buffered data followed by an optional error.
+struct ReplayBody {
+    data: Option<Bytes>,
+    error: Option<Status>,
+}
+
+impl ReplayBody {
+    fn new(data: Bytes) -> Self {
+        Self {
+            data: Some(data),
+            error: None,
+        }
+    }
+}
+
+impl Body for ReplayBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        if let Some(data) = self.data.take().filter(|data| !data.is_empty()) {
+            return Poll::Ready(Some(Ok(data)));
+        }
+
+        Poll::Ready(self.error.take().map(Err))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        !matches!(&self.data, Some(data) if !data.is_empty()) && self.error.is_none()
+    }
+}
+
+/// A [`Layer`] that wraps services in a [`RequestLoggingService`].
+#[derive(Debug, Clone)]
+pub struct RequestLoggingLayer {
+    level: Level,
+    authority: Option<Arc<str>>,
+}
+
+impl RequestLoggingLayer {
+    /// Log every request with an event at `level`.
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            authority: None,
+        }
+    }
+
+    /// Log `authority`, e.g. the `host:port` of the service, instead of the authority of the
+    /// request URI, which tonic's generated clients leave out.
+    pub fn with_authority(self, authority: impl Into<String>) -> Self {
+        Self {
+            authority: Some(authority.into().into()),
+            ..self
+        }
+    }
+}
+
+impl<S> Layer<S> for RequestLoggingLayer {
+    type Service = RequestLoggingService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestLoggingService {
+            inner,
+            level: self.level,
+            authority: self.authority.clone(),
+        }
+    }
+}
+
+/// Logs every request once its response headers have arrived, or once it has failed.
+///
+/// The event carries the method path, authority and content type of the request, the HTTP
+/// status of the response, how long the response took and its `grpc-status`. The gRPC status is
+/// only part of responses without messages: other responses send it in their trailers,
+/// which are not awaited.
+///
+/// The authority is the one set with [`RequestLoggingLayer::with_authority`], or else the
+/// authority of the request URI, which is empty for requests of tonic's generated clients.
+#[derive(Debug, Clone)]
+pub struct RequestLoggingService<S> {
+    inner: S,
+    level: Level,
+    authority: Option<Arc<str>>,
+}
+
+impl<S> RequestLoggingService<S> {
+    /// Wrap `inner`, see [`RequestLoggingLayer::new`].
+    pub fn new(inner: S, level: Level) -> Self {
+        RequestLoggingLayer::new(level).layer(inner)
+    }
+}
+
+impl<S, B, R> Service<Request<B>> for RequestLoggingService<S>
+where
+    S: Service<Request<B>, Response = http::Response<R>>,
+    S::Future: Send + 'static,
+    S::Error: Display + Send + 'static,
+    R: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Response, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let log = RequestLog::new(self.level, self.authority.as_deref(), &request);
+        Box::pin(log.around(self.inner.call(request)))
+    }
+}
+
+/// Emit an event at a level that is only known at runtime.
+macro_rules! event_at {
+    ($level:expr, $($fields:tt)+) => {{
+        let level = $level;
+        if level == Level::ERROR {
+            tracing::event!(Level::ERROR, $($fields)+)
+        } else if level == Level::WARN {
+            tracing::event!(Level::WARN, $($fields)+)
+        } else if level == Level::INFO {
+            tracing::event!(Level::INFO, $($fields)+)
+        } else if level == Level::DEBUG {
+            tracing::event!(Level::DEBUG, $($fields)+)
+        } else {
+            tracing::event!(Level::TRACE, $($fields)+)
+        }
+    }};
+}
+
+/// What is logged about a request, taken before the request is handed to the inner service.
+pub(crate) struct RequestLog {
+    level: Level,
+    path: String,
+    authority: String,
+    content_type: String,
+}
+
+impl RequestLog {
+    /// Take what is logged about `request`, logging `authority` if there is one instead of
+    /// the authority of the request URI.
+    pub(crate) fn new<B>(level: Level, authority: Option<&str>, request: &Request<B>) -> Self {
+        let content_type = request
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+
+        Self {
+            level,
+            path: request.uri().path().to_string(),
+            authority: match authority {
+                Some(authority) => authority.to_string(),
+                None => request
+                    .uri()
+                    .authority()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+            },
+            content_type: content_type.to_string(),
+        }
+    }
+
+    /// Log the outcome of `response`.
+    pub(crate) async fn around<R, E: Display>(
+        self,
+        response: impl Future<Output = Result<http::Response<R>, E>>,
+    ) -> Result<http::Response<R>, E> {
+        let start = Instant::now();
+        let response = response.await;
+        let elapsed = start.elapsed();
+
+        match response {
+            Ok(ref response) => {
+                let grpc_status = response
+                    .headers()
+                    .get("grpc-status")
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default();
+                event_at!(
+                    self.level,
+                    path = %self.path,
+                    authority = %self.authority,
+                    content_type = %self.content_type,
+                    status = response.status().as_u16(),
+                    grpc_status = %grpc_status,
+                    elapsed = ?elapsed,
+                    "grpc request"
+                );
+            }
+            Err(ref err) => event_at!(
+                self.level,
+                path = %self.path,
+                authority = %self.authority,
+                content_type = %self.content_type,
+                error = %err,
+                elapsed = ?elapsed,
+                "grpc request failed"
+            ),
+        }
+
+        response
+    }
+}
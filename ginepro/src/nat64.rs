@@ -0,0 +1,140 @@
+//! Implements [`LookupService`] for IPv6-only clients that reach IPv4 services through NAT64.
+
+use crate::{LookupService, ServiceDefinition};
+use anyhow::Context;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Instant;
+use trust_dns_resolver::TokioAsyncResolver;
+
+/// The well-known NAT64 prefix `64:ff9b::/96` of RFC 6052.
+pub const WELL_KNOWN_NAT64_PREFIX: Ipv6Addr = Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0);
+
+// The name that only has IPv4 addresses, used to discover the NAT64 prefix (RFC 7050).
+static IPV4_ONLY_NAME: &str = "ipv4only.arpa.";
+
+// The IPv4 addresses of `IPV4_ONLY_NAME`.
+static IPV4_ONLY_ADDRESSES: [Ipv4Addr; 2] =
+    [Ipv4Addr::new(192, 0, 0, 170), Ipv4Addr::new(192, 0, 0, 171)];
+
+/// Wraps a [`LookupService`] and replaces every IPv4 endpoint it resolves with its NAT64 address.
+///
+/// IPv6 endpoints are returned as they are. The IPv4 endpoints are left out by default:
+/// the channel balances requests over all endpoints and does not skip the ones it cannot
+/// connect to, so on an IPv6-only network a share of the requests would fail.
+/// Only `/96` prefixes are supported: the IPv4 address makes up the last 32 bits of the
+/// synthesized address.
+pub struct Nat64LookupService<L> {
+    inner: L,
+    prefix: Ipv6Addr,
+    keep_ipv4_endpoints: bool,
+}
+
+impl<L: LookupService> Nat64LookupService<L> {
+    /// Synthesize IPv6 addresses from the first 96 bits of `prefix`.
+    pub fn new(inner: L, prefix: Ipv6Addr) -> Self {
+        Self {
+            inner,
+            prefix,
+            keep_ipv4_endpoints: false,
+        }
+    }
+
+    /// Also return the IPv4 endpoints next to their NAT64 addresses, e.g. for clients that
+    /// move between IPv6-only and dual-stack networks.
+    /// Requests sent to the endpoints that are unreachable from the client network fail.
+    /// Default is `false`.
+    pub fn with_ipv4_endpoints(self, keep_ipv4_endpoints: bool) -> Self {
+        Self {
+            keep_ipv4_endpoints,
+            ..self
+        }
+    }
+
+    /// Synthesize IPv6 addresses with the [`WELL_KNOWN_NAT64_PREFIX`].
+    pub fn with_well_known_prefix(inner: L) -> Self {
+        Self::new(inner, WELL_KNOWN_NAT64_PREFIX)
+    }
+
+    /// Discover the NAT64 prefix of the network as described in RFC 7050,
+    /// by resolving `ipv4only.arpa` with the system DNS configuration.
+    pub async fn discover_prefix(inner: L) -> Result<Self, anyhow::Error> {
+        let dns = TokioAsyncResolver::tokio_from_system_conf()
+            .context("failed to read dns services from system configuration")?;
+        let lookup = dns
+            .ipv6_lookup(IPV4_ONLY_NAME)
+            .await
+            .context("failed to resolve ipv4only.arpa, the network may not use NAT64")?;
+
+        let prefix = lookup
+            .iter()
+            .find_map(|address| {
+                let octets = address.octets();
+                let embedded = Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]);
+                IPV4_ONLY_ADDRESSES
+                    .contains(&embedded)
+                    .then(|| nat64_prefix(*address))
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!("ipv4only.arpa did not resolve to a /96 NAT64 address")
+            })?;
+
+        Ok(Self::new(inner, prefix))
+    }
+
+    /// The IPv6 address that the NAT64 gateway translates to `address`.
+    fn synthesize(&self, address: Ipv4Addr) -> Ipv6Addr {
+        let mut octets = nat64_prefix(self.prefix).octets();
+        octets[12..].copy_from_slice(&address.octets());
+        Ipv6Addr::from(octets)
+    }
+
+    /// Replace or complement the IPv4 `endpoints` with their NAT64 addresses.
+    fn translate(&self, endpoints: HashSet<SocketAddr>) -> HashSet<SocketAddr> {
+        let synthesized: Vec<SocketAddr> = endpoints
+            .iter()
+            .filter_map(|endpoint| match endpoint.ip() {
+                IpAddr::V4(address) => Some(SocketAddr::new(
+                    IpAddr::V6(self.synthesize(address)),
+                    endpoint.port(),
+                )),
+                IpAddr::V6(_) => None,
+            })
+            .collect();
+
+        endpoints
+            .into_iter()
+            .filter(|endpoint| self.keep_ipv4_endpoints || endpoint.is_ipv6())
+            .chain(synthesized)
+            .collect()
+    }
+}
+
+/// Clear everything but the first 96 bits of `address`.
+fn nat64_prefix(address: Ipv6Addr) -> Ipv6Addr {
+    let mut octets = address.octets();
+    octets[12..].copy_from_slice(&[0; 4]);
+    Ipv6Addr::from(octets)
+}
+
+#[async_trait::async_trait]
+impl<L: LookupService + Send + Sync> LookupService for Nat64LookupService<L> {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let endpoints = self.inner.resolve_service_endpoints(definition).await?;
+        Ok(self.translate(endpoints))
+    }
+
+    async fn resolve_service_endpoints_with_expiry(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<(HashSet<SocketAddr>, Option<Instant>), anyhow::Error> {
+        let (endpoints, expires_at) = self
+            .inner
+            .resolve_service_endpoints_with_expiry(definition)
+            .await?;
+        Ok((self.translate(endpoints), expires_at))
+    }
+}
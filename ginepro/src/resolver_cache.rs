@@ -0,0 +1,157 @@
+//! A cache of resolved endpoints that can be shared between multiple [`LoadBalancedChannel`](crate::LoadBalancedChannel)s.
+
+use lru::LruCache;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+
+/// Identifies a service by its hostname and port.
+type CacheKey = (String, u16);
+
+/// A hostname and port together with the endpoints resolved for them, until when they are fresh
+/// and when their records expire, if known.
+type CacheEntries = LruCache<CacheKey, (Vec<SocketAddr>, Instant, Option<std::time::Instant>)>;
+
+/// The resolved endpoints and when their records expire, if known.
+type Resolved = (Vec<SocketAddr>, Option<std::time::Instant>);
+
+/// Caches the endpoints resolved for a hostname and port, so that channels pointing
+/// to the same service do not each have to run their own lookup.
+///
+/// Share a single instance between channels with
+/// [`LoadBalancedChannelBuilder::with_resolver_cache`](crate::LoadBalancedChannelBuilder::with_resolver_cache).
+/// When several channels miss the cache at the same time, only one of them queries its
+/// [`LookupService`](crate::LookupService) while the others wait for and reuse its result.
+///
+/// Entries are keyed by hostname and port only, not by the [`LookupService`](crate::LookupService)
+/// that resolved them. Channels that use different lookup services for the same
+/// service should not share a cache, as they would see each other's results.
+///
+/// ```rust
+/// use ginepro::SharedResolverCache;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let cache = Arc::new(SharedResolverCache::new(128, Duration::from_secs(30)));
+/// ```
+pub struct SharedResolverCache {
+    /// Maps a hostname and port to its endpoints and until when they are fresh.
+    entries: Mutex<CacheEntries>,
+    /// Held while a service is being resolved so that concurrent misses wait for that lookup.
+    /// Entries are removed once their lookup completes.
+    lookups: Mutex<HashMap<CacheKey, Arc<tokio::sync::Mutex<()>>>>,
+    ttl: Duration,
+}
+
+impl SharedResolverCache {
+    /// Construct a cache holding the endpoints of at most `capacity` services.
+    ///
+    /// An entry is considered fresh until its records expire, if the
+    /// [`LookupService`](crate::LookupService) reports when they do, like [`DnsResolver`](crate::DnsResolver).
+    /// Otherwise it is considered fresh for `ttl` after it has been resolved, which
+    /// should not be longer than the TTL of the records being cached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        let capacity = NonZeroUsize::new(capacity).expect("cache capacity must not be zero");
+
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            lookups: Mutex::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    /// Return the fresh cached endpoints of `hostname` and `port` and when their records
+    /// expire, or store and return the result of `resolve` if there are none.
+    /// At most one `resolve` runs at a time for the same hostname and port.
+    pub(crate) async fn get_or_resolve<F>(
+        &self,
+        hostname: &str,
+        port: u16,
+        resolve: F,
+    ) -> Result<Resolved, anyhow::Error>
+    where
+        F: Future<Output = Result<Resolved, anyhow::Error>>,
+    {
+        let key = (hostname.to_string(), port);
+
+        if let Some(endpoints) = self.get(&key) {
+            return Ok(endpoints);
+        }
+
+        let lookup = Arc::clone(
+            self.lookups
+                .lock()
+                .expect("resolver cache lock poisoned")
+                .entry(key.clone())
+                .or_default(),
+        );
+        let _lookup = lookup.lock().await;
+        let _in_flight = InFlightLookup {
+            cache: self,
+            key: &key,
+            lookup: &lookup,
+        };
+
+        // Another lookup may have completed while we were waiting.
+        if let Some(endpoints) = self.get(&key) {
+            return Ok(endpoints);
+        }
+
+        let (endpoints, expires_at) = resolve.await?;
+        let fresh_until = match expires_at {
+            Some(expires_at) => Instant::from_std(expires_at),
+            None => Instant::now() + self.ttl,
+        };
+        self.entries
+            .lock()
+            .expect("resolver cache lock poisoned")
+            .put(key.clone(), (endpoints.clone(), fresh_until, expires_at));
+
+        Ok((endpoints, expires_at))
+    }
+
+    /// Return the cached endpoints of `key` if they are still fresh.
+    fn get(&self, key: &CacheKey) -> Option<Resolved> {
+        let mut entries = self.entries.lock().expect("resolver cache lock poisoned");
+
+        match entries.get(key) {
+            Some((endpoints, fresh_until, expires_at)) if Instant::now() < *fresh_until => {
+                tracing::debug!("using cached endpoints for host: {}", key.0);
+                Some((endpoints.clone(), *expires_at))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Removes the in-flight entry of a lookup from the cache when the lookup completes,
+/// including when it fails or is cancelled.
+struct InFlightLookup<'a> {
+    cache: &'a SharedResolverCache,
+    key: &'a CacheKey,
+    lookup: &'a Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Drop for InFlightLookup<'_> {
+    fn drop(&mut self) {
+        let mut lookups = self
+            .cache
+            .lookups
+            .lock()
+            .expect("resolver cache lock poisoned");
+
+        // Lookups that were waiting on this entry may have replaced it already.
+        if let Some(lookup) = lookups.get(self.key) {
+            if Arc::ptr_eq(lookup, self.lookup) {
+                lookups.remove(self.key);
+            }
+        }
+    }
+}
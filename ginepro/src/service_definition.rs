@@ -1,6 +1,9 @@
+use std::fmt;
+use std::net::Ipv6Addr;
+
 /// Defines a gRPC service with a `hostname` and a `port`.
 /// The hostname will be resolved to the concrete ips of the service servers.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ServiceDefinition {
     /// The hostname of the service.
     pub hostname: String,
@@ -8,6 +11,28 @@ pub struct ServiceDefinition {
     pub port: u16,
 }
 
+impl ServiceDefinition {
+    /// A service on `port` of the IPv4 loopback address `127.0.0.1`.
+    pub fn localhost(port: u16) -> Self {
+        Self::from(("127.0.0.1", port))
+    }
+
+    /// A service on `port` of the IPv6 loopback address `::1`.
+    ///
+    /// The hostname is the bare address, it is only wrapped in brackets when formatted:
+    ///
+    /// ```rust
+    /// use ginepro::ServiceDefinition;
+    ///
+    /// let definition = ServiceDefinition::loopback_v6(5000);
+    /// assert_eq!(definition.hostname, "::1");
+    /// assert_eq!(definition.to_string(), "[::1]:5000");
+    /// ```
+    pub fn loopback_v6(port: u16) -> Self {
+        Self::from(("::1", port))
+    }
+}
+
 impl From<(&str, u16)> for ServiceDefinition {
     fn from(service: (&str, u16)) -> Self {
         Self {
@@ -16,3 +41,70 @@ impl From<(&str, u16)> for ServiceDefinition {
         }
     }
 }
+
+/// Formats the service as `hostname:port`, wrapping IPv6 addresses in brackets so that the
+/// port can be told apart.
+///
+/// ```rust
+/// use ginepro::ServiceDefinition;
+///
+/// assert_eq!(ServiceDefinition::from(("my.service.uri", 5000)).to_string(), "my.service.uri:5000");
+/// assert_eq!(ServiceDefinition::from(("127.0.0.1", 5000)).to_string(), "127.0.0.1:5000");
+/// assert_eq!(ServiceDefinition::from(("::1", 5000)).to_string(), "[::1]:5000");
+/// ```
+impl fmt::Display for ServiceDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.hostname.parse::<Ipv6Addr>().is_ok() {
+            write!(f, "[{}]:{}", self.hostname, self.port)
+        } else {
+            write!(f, "{}:{}", self.hostname, self.port)
+        }
+    }
+}
+
+#[cfg(feature = "url")]
+impl ServiceDefinition {
+    /// Construct a [`ServiceDefinition`] from the host and port of `url`, e.g. `grpc://my.service.uri:5000`.
+    ///
+    /// Any scheme is accepted. Without a port, the port defaults to 80 for `grpc` and `http`,
+    /// and to 443 for `grpcs` and `https`; for other schemes the port is required.
+    pub fn from_url(url: &url::Url) -> Result<ServiceDefinition, anyhow::Error> {
+        let hostname = match url.host() {
+            Some(url::Host::Domain(domain)) => domain.to_string(),
+            Some(url::Host::Ipv4(address)) => address.to_string(),
+            Some(url::Host::Ipv6(address)) => address.to_string(),
+            None => return Err(anyhow::anyhow!("'{}' has no host", url)),
+        };
+        let port = url
+            .port_or_known_default()
+            .or(match url.scheme() {
+                "grpc" => Some(80),
+                "grpcs" => Some(443),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("'{}' has no port", url))?;
+
+        Ok(ServiceDefinition { hostname, port })
+    }
+}
+
+/// See [`ServiceDefinition::from_url`].
+#[cfg(feature = "url")]
+impl std::convert::TryFrom<url::Url> for ServiceDefinition {
+    type Error = anyhow::Error;
+
+    fn try_from(url: url::Url) -> Result<Self, Self::Error> {
+        ServiceDefinition::from_url(&url)
+    }
+}
+
+/// Produces a `grpc://hostname:port` URL.
+/// Fails if the hostname is not a valid URL host, e.g. because it contains spaces.
+#[cfg(feature = "url")]
+impl std::convert::TryFrom<ServiceDefinition> for url::Url {
+    type Error = url::ParseError;
+
+    fn try_from(definition: ServiceDefinition) -> Result<Self, Self::Error> {
+        url::Url::parse(&format!("grpc://{}", definition))
+    }
+}
@@ -1,9 +1,52 @@
-use crate::{LookupService, ServiceDefinition};
-use std::collections::HashSet;
+#[cfg(feature = "opentelemetry")]
+use crate::telemetry::ProbeSpan;
+use crate::{
+    EndpointChangeEvent, EndpointSortStrategy, GineproError, LookupService, ServiceDefinition,
+    SharedResolverCache,
+};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
 use std::net::SocketAddr;
-use tokio::sync::mpsc::Sender;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::sync::{broadcast, mpsc::Sender, Mutex, OwnedSemaphorePermit, Semaphore};
 use tonic::transport::{channel::Endpoint, ClientTlsConfig};
 use tower::discover::Change;
+use tower::Service;
+use tracing::Level;
+
+// How long to wait between probe cycles while no endpoints are known,
+// if `no_delay_on_idle` is enabled.
+static IDLE_PROBE_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_millis(100);
+
+// The shortest wait between probe cycles when probing as endpoints expire or their connections
+// are replaced, so that records with a TTL of zero or a short server max age do not make us
+// probe continuously.
+static MIN_EXPIRY_PROBE_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_secs(1);
+
+// The share of the server's maximum connection age after which connections are replaced.
+// gRPC servers jitter the maximum age by up to 10%, the rest leaves time for in-flight requests.
+const PREEMPTIVE_RECONNECT_FRACTION: f64 = 0.8;
+
+// The levels the probe can log at, from the least to the most verbose,
+// indexed by the value stored in `log_level`.
+static LOG_LEVELS: [Level; 5] = [
+    Level::ERROR,
+    Level::WARN,
+    Level::INFO,
+    Level::DEBUG,
+    Level::TRACE,
+];
+
+/// The resolved endpoints and when they expire, if known.
+type Resolved = (Vec<SocketAddr>, Option<Instant>);
+
+/// Called once the channel has endpoints for the first time.
+pub(crate) type ChannelReadyCallback = Box<dyn FnOnce() + Send>;
 
 /// [`GrpcServiceProbe`] looks up IP addresses associated with the configured `host_name`
 /// once every `probe_interval`.
@@ -19,19 +62,113 @@ use tower::discover::Change;
 ///       and we have not instructed the removal of that server's address from the
 ///       set of endpoints known to the tonic client.
 ///
+/// [`GrpcServiceProbe`] also implements [`Service`], resolving a hostname to the endpoints
+/// of the service on the configured port. This allows tower middleware such as timeouts
+/// and retries to be applied to a single lookup.
+/// The implementation only exists for external callers: the probe cycles of
+/// [`GrpcServiceProbe::probe`] and [`GrpcServiceProbe::probe_once`] do not go through it.
+///
+/// Cloning a [`GrpcServiceProbe`] shares its endpoint set and its reporter: every clone can
+/// drive probe cycles, e.g. one on a timer with [`GrpcServiceProbe::probe`] and another on demand
+/// with [`GrpcServiceProbe::probe_once`], and each cycle only reports the changes since the
+/// previous cycle of any clone.
+///
+/// ```rust
+/// use ginepro::{DnsResolver, GrpcServiceProbe, GrpcServiceProbeConfig};
+/// use std::time::Duration;
+/// use tower::{Service, ServiceExt};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = GrpcServiceProbeConfig {
+///         service_definition: ("localhost", 5000).into(),
+///         dns_lookup: DnsResolver::from_system_config()
+///             .await
+///             .expect("failed to read system conf"),
+///         probe_interval: Duration::from_secs(10),
+///         endpoint_timeout: None,
+///     };
+///     let (_channel, sender) = tonic::transport::Channel::balance_channel(
+///         ginepro::DEFAULT_ENDPOINT_CHANNEL_SIZE,
+///     );
+///     let probe = GrpcServiceProbe::new_with_reporter(config, sender);
+///
+///     let mut probe = tower::timeout::Timeout::new(probe, Duration::from_secs(1));
+///     let _endpoints = probe.ready().await.unwrap().call("localhost").await;
+/// }
+/// ```
+///
+/// The probe only emits the events up to its log level, which is shared by all clones and
+/// can be changed at runtime with [`GrpcServiceProbe::set_log_level`].
 pub struct GrpcServiceProbe<Lookup>
 where
     Lookup: LookupService,
 {
     service_definition: ServiceDefinition,
+    additional_services: Vec<ServiceDefinition>,
     scheme: http::uri::Scheme,
-    dns_lookup: Lookup,
+    dns_lookup: Arc<Lookup>,
+    resolver_cache: Option<Arc<SharedResolverCache>>,
     probe_interval: tokio::time::Duration,
     endpoint_timeout: Option<tokio::time::Duration>,
-    /// The set of last reported endpoints by `dns_lookup`.
-    endpoints: HashSet<SocketAddr>,
+    endpoint_sort_strategy: EndpointSortStrategy,
+    tcp_probe_timeout: Option<tokio::time::Duration>,
+    connection_backlog: Option<Arc<Semaphore>>,
+    state: Arc<Mutex<ProbeState>>,
     endpoint_reporter: Sender<Change<SocketAddr, Endpoint>>,
+    endpoint_change_publisher: Option<broadcast::Sender<EndpointChangeEvent>>,
     tls_config: Option<ClientTlsConfig>,
+    no_delay_on_idle: bool,
+    ttl_aware_removal: bool,
+    reconnect_after: Option<tokio::time::Duration>,
+    peer_count: Option<Arc<AtomicUsize>>,
+    log_level: Arc<AtomicU8>,
+    endpoint_added_hook: Option<fn(SocketAddr)>,
+    endpoint_removed_hook: Option<fn(SocketAddr)>,
+    channel_ready_callback: Arc<std::sync::Mutex<Option<ChannelReadyCallback>>>,
+    #[cfg(feature = "opentelemetry")]
+    tracer: Option<Arc<opentelemetry::global::BoxedTracer>>,
+}
+
+/// The state shared by all clones of a [`GrpcServiceProbe`].
+struct ProbeState {
+    /// The set of last reported endpoints by `dns_lookup`.
+    endpoints: HashSet<SocketAddr>,
+    /// When the last reported endpoints expire, if known.
+    expires_at: Option<Instant>,
+    /// When every endpoint was last submitted to tonic, which opens a new connection.
+    submitted_at: HashMap<SocketAddr, Instant>,
+}
+
+impl<Lookup: LookupService> Clone for GrpcServiceProbe<Lookup> {
+    fn clone(&self) -> Self {
+        Self {
+            service_definition: self.service_definition.clone(),
+            additional_services: self.additional_services.clone(),
+            scheme: self.scheme.clone(),
+            dns_lookup: Arc::clone(&self.dns_lookup),
+            resolver_cache: self.resolver_cache.clone(),
+            probe_interval: self.probe_interval,
+            endpoint_timeout: self.endpoint_timeout,
+            endpoint_sort_strategy: self.endpoint_sort_strategy,
+            tcp_probe_timeout: self.tcp_probe_timeout,
+            connection_backlog: self.connection_backlog.clone(),
+            state: Arc::clone(&self.state),
+            endpoint_reporter: self.endpoint_reporter.clone(),
+            endpoint_change_publisher: self.endpoint_change_publisher.clone(),
+            tls_config: self.tls_config.clone(),
+            no_delay_on_idle: self.no_delay_on_idle,
+            ttl_aware_removal: self.ttl_aware_removal,
+            reconnect_after: self.reconnect_after,
+            peer_count: self.peer_count.clone(),
+            log_level: Arc::clone(&self.log_level),
+            endpoint_added_hook: self.endpoint_added_hook,
+            endpoint_removed_hook: self.endpoint_removed_hook,
+            channel_ready_callback: Arc::clone(&self.channel_ready_callback),
+            #[cfg(feature = "opentelemetry")]
+            tracer: self.tracer.clone(),
+        }
+    }
 }
 
 /// Config parameters to customize the behavior of `GrpcServiceProbe`.
@@ -50,7 +187,81 @@ where
     pub endpoint_timeout: Option<tokio::time::Duration>,
 }
 
-impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
+/// Implements [`Service`] for hostnames, resolving them to the endpoints on the port of
+/// [`GrpcServiceProbeConfig::service_definition`] like a [`GrpcServiceProbe`] would, but
+/// without reporting them to a channel.
+///
+/// Lookups go through the resolver cache set with [`ResolveService::with_resolver_cache`],
+/// if there is one. The other options of the config only apply to probes and are ignored.
+pub struct ResolveService<Lookup> {
+    dns_lookup: Arc<Lookup>,
+    resolver_cache: Option<Arc<SharedResolverCache>>,
+    port: u16,
+}
+
+impl<Lookup> Clone for ResolveService<Lookup> {
+    fn clone(&self) -> Self {
+        Self {
+            dns_lookup: Arc::clone(&self.dns_lookup),
+            resolver_cache: self.resolver_cache.clone(),
+            port: self.port,
+        }
+    }
+}
+
+impl<Lookup: LookupService> From<GrpcServiceProbeConfig<Lookup>> for ResolveService<Lookup> {
+    fn from(config: GrpcServiceProbeConfig<Lookup>) -> Self {
+        Self {
+            dns_lookup: Arc::new(config.dns_lookup),
+            resolver_cache: None,
+            port: config.service_definition.port,
+        }
+    }
+}
+
+impl<Lookup> ResolveService<Lookup> {
+    /// Go through `resolver_cache` for every lookup, see [`GrpcServiceProbe::with_resolver_cache`].
+    pub fn with_resolver_cache(self, resolver_cache: Arc<SharedResolverCache>) -> Self {
+        Self {
+            resolver_cache: Some(resolver_cache),
+            ..self
+        }
+    }
+}
+
+impl<Lookup> Service<String> for ResolveService<Lookup>
+where
+    Lookup: LookupService + Send + Sync + 'static,
+{
+    type Response = Vec<SocketAddr>;
+    type Error = GineproError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, hostname: String) -> Self::Future {
+        let dns_lookup = Arc::clone(&self.dns_lookup);
+        let resolver_cache = self.resolver_cache.clone();
+        let definition = ServiceDefinition {
+            hostname,
+            port: self.port,
+        };
+
+        Box::pin(async move {
+            resolve_through_cache(dns_lookup, resolver_cache, &definition)
+                .await
+                .map(|(endpoints, _)| endpoints)
+                .map_err(|err| GineproError::LookupFailed {
+                    hostname: definition.hostname,
+                    source: err.into(),
+                })
+        })
+    }
+}
+
+impl<Lookup: LookupService + Send + Sync + 'static> GrpcServiceProbe<Lookup> {
     /// Construct `GrpcServiceProbe` with a `GrpcServiceProbeConfig` and
     /// the channel `endpoint_reporter` that will send endpoint changes.
     pub fn new_with_reporter(
@@ -59,13 +270,33 @@ impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
     ) -> GrpcServiceProbe<Lookup> {
         Self {
             service_definition: config.service_definition,
-            dns_lookup: config.dns_lookup,
+            additional_services: Vec::new(),
+            dns_lookup: Arc::new(config.dns_lookup),
+            resolver_cache: None,
             probe_interval: config.probe_interval,
             endpoint_timeout: config.endpoint_timeout,
-            endpoints: HashSet::new(),
+            endpoint_sort_strategy: EndpointSortStrategy::default(),
+            tcp_probe_timeout: None,
+            connection_backlog: None,
+            state: Arc::new(Mutex::new(ProbeState {
+                endpoints: HashSet::new(),
+                expires_at: None,
+                submitted_at: HashMap::new(),
+            })),
             endpoint_reporter,
+            endpoint_change_publisher: None,
             scheme: http::uri::Scheme::HTTP,
             tls_config: None,
+            no_delay_on_idle: false,
+            ttl_aware_removal: false,
+            reconnect_after: None,
+            peer_count: None,
+            log_level: Arc::new(AtomicU8::new(log_level_index(Level::DEBUG))),
+            endpoint_added_hook: None,
+            endpoint_removed_hook: None,
+            channel_ready_callback: Arc::new(std::sync::Mutex::new(None)),
+            #[cfg(feature = "opentelemetry")]
+            tracer: None,
         }
     }
 
@@ -78,56 +309,390 @@ impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
         }
     }
 
+    /// Share the resolved addresses with the other probes using `resolver_cache`,
+    /// so that probes of the same hostname and port only look it up once.
+    pub fn with_resolver_cache(
+        self,
+        resolver_cache: Arc<SharedResolverCache>,
+    ) -> GrpcServiceProbe<Lookup> {
+        Self {
+            resolver_cache: Some(resolver_cache),
+            ..self
+        }
+    }
+
+    /// Report new endpoints in the order of `endpoint_sort_strategy`.
+    /// Default is [`EndpointSortStrategy::None`].
+    pub fn with_endpoint_sort_strategy(
+        self,
+        endpoint_sort_strategy: EndpointSortStrategy,
+    ) -> GrpcServiceProbe<Lookup> {
+        Self {
+            endpoint_sort_strategy,
+            ..self
+        }
+    }
+
+    /// Publish every endpoint change on `publisher` once it has been reported to tonic.
+    pub fn with_endpoint_change_publisher(
+        self,
+        publisher: broadcast::Sender<EndpointChangeEvent>,
+    ) -> GrpcServiceProbe<Lookup> {
+        Self {
+            endpoint_change_publisher: Some(publisher),
+            ..self
+        }
+    }
+
+    /// Also report the endpoints of `service_definition` on every probe cycle.
+    /// All services are resolved concurrently.
+    pub fn with_additional_service(
+        mut self,
+        service_definition: ServiceDefinition,
+    ) -> GrpcServiceProbe<Lookup> {
+        self.additional_services.push(service_definition);
+        self
+    }
+
+    /// Only report new endpoints that accept a TCP connection within `timeout`.
+    /// Endpoints that do not are tried again on the next probe cycle.
+    pub fn with_tcp_probe(self, timeout: tokio::time::Duration) -> GrpcServiceProbe<Lookup> {
+        Self {
+            tcp_probe_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Open at most `backlog` connections at the same time for [`with_tcp_probe`](Self::with_tcp_probe),
+    /// e.g. so that a large DNS response does not exhaust the TCP connection queue. The connections of the channel itself are opened
+    /// by tonic and are not limited.
+    ///
+    /// [`EndpointSortStrategy::ByLatency`] measurements share the same limit.
+    /// A `backlog` above [`Semaphore::MAX_PERMITS`] is capped to it.
+    pub fn with_connection_backlog(self, backlog: NonZeroUsize) -> GrpcServiceProbe<Lookup> {
+        let backlog = backlog.get().min(Semaphore::MAX_PERMITS);
+        Self {
+            connection_backlog: Some(Arc::new(Semaphore::new(backlog))),
+            ..self
+        }
+    }
+
+    /// Probe again after a short delay instead of `probe_interval` while no endpoints are known,
+    /// so that a service that has no endpoints yet becomes usable as soon as it has any.
+    ///
+    /// The very first probe cycle always runs right away.
+    pub fn with_no_delay_on_idle(self, no_delay_on_idle: bool) -> GrpcServiceProbe<Lookup> {
+        Self {
+            no_delay_on_idle,
+            ..self
+        }
+    }
+
+    /// Probe again as soon as the resolved endpoints expire, e.g. when the TTL of their
+    /// DNS records elapses, if that is before `probe_interval`.
+    /// Endpoints that are gone from the lookup by then are removed right away.
+    pub fn with_ttl_aware_removal(self, ttl_aware_removal: bool) -> GrpcServiceProbe<Lookup> {
+        Self {
+            ttl_aware_removal,
+            ..self
+        }
+    }
+
+    /// Replace the connection to every endpoint before it reaches `server_max_age`, the age
+    /// at which the server closes connections, e.g. `max_connection_age` in gRPC servers.
+    ///
+    /// Connections are replaced after 80% of `server_max_age`, by submitting the endpoint to
+    /// tonic again: new requests go over a new connection while requests in flight finish
+    /// on the old one. This is not reported as an endpoint change.
+    /// Replacing connections never shortens the wait between probe cycles below one second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `server_max_age` is zero.
+    pub fn with_preemptive_reconnect(
+        self,
+        server_max_age: tokio::time::Duration,
+    ) -> GrpcServiceProbe<Lookup> {
+        assert!(!server_max_age.is_zero(), "server max age must not be zero");
+        Self {
+            reconnect_after: Some(server_max_age.mul_f64(PREEMPTIVE_RECONNECT_FRACTION)),
+            ..self
+        }
+    }
+
+    /// Store the number of endpoints reported to the channel in `peer_count` after every change.
+    pub(crate) fn with_peer_count(self, peer_count: Arc<AtomicUsize>) -> GrpcServiceProbe<Lookup> {
+        Self {
+            peer_count: Some(peer_count),
+            ..self
+        }
+    }
+
+    /// Only emit the events of the probe loop up to `level`, e.g. [`Level::TRACE`] to also log
+    /// the endpoints resolved in every cycle. Default is [`Level::DEBUG`].
+    ///
+    /// The level is shared by all clones of the probe, so a clone kept as a handle changes
+    /// the verbosity of a probe that is already running.
+    ///
+    /// This only filters events on top of the subscriber: events above the level enabled by the
+    /// subscriber are never recorded, whatever the level of the probe.
+    pub fn set_log_level(&self, level: Level) {
+        store_log_level(&self.log_level, level);
+    }
+
+    /// The log level shared by all clones of the probe, for
+    /// [`LoadBalancedChannel::set_log_level`](crate::LoadBalancedChannel::set_log_level).
+    pub(crate) fn shared_log_level(&self) -> Arc<AtomicU8> {
+        Arc::clone(&self.log_level)
+    }
+
+    /// Whether events at `level` are emitted with the current log level.
+    fn logs_at(&self, level: Level) -> bool {
+        logs_at(&self.log_level, level)
+    }
+
+    /// Call `hook` with every endpoint that has been added to the channel.
+    pub fn on_endpoint_added(self, hook: fn(SocketAddr)) -> GrpcServiceProbe<Lookup> {
+        Self {
+            endpoint_added_hook: Some(hook),
+            ..self
+        }
+    }
+
+    /// Call `hook` with every endpoint that has been removed from the channel.
+    pub fn on_endpoint_removed(self, hook: fn(SocketAddr)) -> GrpcServiceProbe<Lookup> {
+        Self {
+            endpoint_removed_hook: Some(hook),
+            ..self
+        }
+    }
+
+    /// Call `callback` the first time endpoints are reported to the channel,
+    /// i.e. once the channel can send requests.
+    ///
+    /// The callback is called at most once, by whichever clone of the probe reports the
+    /// first endpoints.
+    pub fn on_channel_ready(
+        self,
+        callback: impl FnOnce() + Send + 'static,
+    ) -> GrpcServiceProbe<Lookup> {
+        Self {
+            channel_ready_callback: Arc::new(std::sync::Mutex::new(Some(Box::new(callback)))),
+            ..self
+        }
+    }
+
+    /// Start a `ginepro.probe` span with `tracer` for every probe cycle.
+    #[cfg(feature = "opentelemetry")]
+    pub fn with_tracer(
+        self,
+        tracer: opentelemetry::global::BoxedTracer,
+    ) -> GrpcServiceProbe<Lookup> {
+        Self {
+            tracer: Some(Arc::new(tracer)),
+            ..self
+        }
+    }
+
     /// Start probing the provided `host_name` for IP address changes.
     /// The function will error if the receiving end of the tonic balance channel
     /// is closed, e.g, the client has been deconstructed.
     /// Any other errors are seen as transient, and therefore retried after `self.probe_interval`.
     pub async fn probe(mut self) -> Result<(), anyhow::Error> {
         loop {
-            match self
-                .dns_lookup
-                .resolve_service_endpoints(&self.service_definition)
-                .await
-            {
-                Ok(endpoints) => {
-                    let changeset = self.create_changeset(&endpoints).await;
+            self.probe_once().await?;
 
-                    // Report the changeset to `tonic` and commit the new endpoints
-                    // if we succeed to report the changeset.
-                    self.report_and_commit(changeset, endpoints).await.map_err(|e| {
+            let delay = self.next_probe_delay(&*self.state.lock().await);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// How long to wait before the next probe cycle.
+    fn next_probe_delay(&self, state: &ProbeState) -> tokio::time::Duration {
+        let mut delay = self.probe_interval;
+
+        if self.no_delay_on_idle && state.endpoints.is_empty() {
+            delay = delay.min(IDLE_PROBE_INTERVAL);
+        }
+
+        if let (true, Some(expires_at)) = (self.ttl_aware_removal, state.expires_at) {
+            let until_expiry = expires_at.saturating_duration_since(Instant::now());
+            delay = delay.min(until_expiry.max(MIN_EXPIRY_PROBE_INTERVAL));
+        }
+
+        if let Some(reconnect_after) = self.reconnect_after {
+            if let Some(oldest) = state.submitted_at.values().min() {
+                let until_reconnect =
+                    (*oldest + reconnect_after).saturating_duration_since(Instant::now());
+                delay = delay.min(until_reconnect.max(MIN_EXPIRY_PROBE_INTERVAL));
+            }
+        }
+
+        delay
+    }
+
+    /// Run a single probe cycle: resolve the endpoints once and report the changes
+    /// since the last cycle of this probe or any of its clones.
+    /// The function will error if the receiving end of the tonic balance channel is closed;
+    /// a failed lookup is only logged.
+    pub async fn probe_once(&mut self) -> Result<(), anyhow::Error> {
+        #[cfg(feature = "opentelemetry")]
+        let mut span = self
+            .tracer
+            .as_ref()
+            .map(|tracer| ProbeSpan::start(tracer, &self.service_definition));
+
+        match self.resolve_all_services().await {
+            Ok((endpoints, expires_at)) => {
+                #[cfg(feature = "opentelemetry")]
+                if let Some(ref mut span) = span {
+                    span.record_endpoint_count(endpoints.len());
+                }
+
+                let mut endpoints: HashSet<SocketAddr> = endpoints.into_iter().collect();
+                if self.logs_at(Level::TRACE) {
+                    tracing::trace!(
+                        "resolved endpoints {:?} for {}",
+                        endpoints,
+                        self.service_definition
+                    );
+                }
+
+                // Hold the state until the changeset is committed, so that clones
+                // never report the same change twice.
+                let mut state = self.state.lock().await;
+
+                if let Some(timeout) = self.tcp_probe_timeout {
+                    // Unreachable endpoints are left out of the commit, so they count as new again.
+                    let unreachable = Self::unreachable_endpoints(
+                        endpoints.difference(&state.endpoints).copied(),
+                        timeout,
+                        &self.connection_backlog,
+                    )
+                    .await;
+                    for endpoint in unreachable {
+                        if self.logs_at(Level::DEBUG) {
+                            tracing::debug!(
+                                "endpoint {} did not accept a TCP connection",
+                                endpoint
+                            );
+                        }
+                        endpoints.remove(&endpoint);
+                    }
+                }
+                let changeset = self.create_changeset(&state, &endpoints).await;
+
+                // Report the changeset to `tonic` and commit the new endpoints
+                // if we succeed to report the changeset.
+                state.expires_at = expires_at;
+                self.report_and_commit(&mut state, changeset, endpoints).await.map_err(|e| {
+                    if self.logs_at(Level::ERROR) {
                         tracing::error!("Failed to report the discovered DNS changeset. The gRPC client has closed the channel therefore the DNS probe loop will exit.\n{:?}", e);
-                        e
-                    })?;
+                    }
+                    #[cfg(feature = "opentelemetry")]
+                    if let Some(mut span) = span.take() {
+                        span.record_error(&e);
+                        span.end();
+                    }
+                    e
+                })?;
+            }
+            Err(err) => {
+                #[cfg(feature = "opentelemetry")]
+                if let Some(ref mut span) = span {
+                    span.record_error(&err);
                 }
-                Err(err) => {
-                    // We received an unrecoverable error, we just log it and continue runnning.
+
+                // We received an unrecoverable error, we just log it and continue runnning.
+                if self.logs_at(Level::WARN) {
                     tracing::warn!("failed to resolve ips from host: {:?}", err);
                 }
             }
+        }
 
-            tokio::time::sleep(self.probe_interval).await;
+        #[cfg(feature = "opentelemetry")]
+        if let Some(span) = span {
+            span.end();
         }
+
+        Ok(())
+    }
+
+    /// Try to open a TCP connection to every endpoint concurrently
+    /// and return the ones that failed to connect within `timeout`.
+    /// The time spent waiting for the `backlog` does not count towards the timeout.
+    async fn unreachable_endpoints(
+        endpoints: impl Iterator<Item = SocketAddr>,
+        timeout: tokio::time::Duration,
+        backlog: &Option<Arc<Semaphore>>,
+    ) -> Vec<SocketAddr> {
+        let connections = endpoints.map(|endpoint| async move {
+            let _permit = connection_permit(backlog).await;
+            let connection =
+                tokio::time::timeout(timeout, tokio::net::TcpStream::connect(endpoint)).await;
+            match connection {
+                Ok(Ok(_)) => None,
+                _ => Some(endpoint),
+            }
+        });
+
+        futures_util::future::join_all(connections)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Resolve the endpoints of all services concurrently.
+    /// Fails if any lookup fails, so that the endpoints of a service are never removed
+    /// only because its lookup failed.
+    /// The endpoints expire with the first service that expires.
+    async fn resolve_all_services(&self) -> Result<Resolved, anyhow::Error> {
+        let lookups = std::iter::once(&self.service_definition)
+            .chain(&self.additional_services)
+            .map(|definition| self.resolve(definition.clone()));
+
+        let mut endpoints = Vec::new();
+        let mut expires_at: Option<Instant> = None;
+        for lookup in futures_util::future::join_all(lookups).await {
+            let (service_endpoints, service_expires_at) = lookup?;
+            endpoints.extend(service_endpoints);
+            expires_at = match (expires_at, service_expires_at) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+
+        Ok((endpoints, expires_at))
+    }
+
+    /// Resolve the endpoints of `definition`, going through the resolver cache if there is one.
+    fn resolve(
+        &self,
+        definition: ServiceDefinition,
+    ) -> impl Future<Output = Result<Resolved, anyhow::Error>> + Send + 'static {
+        let dns_lookup = Arc::clone(&self.dns_lookup);
+        let resolver_cache = self.resolver_cache.clone();
+
+        async move { resolve_through_cache(dns_lookup, resolver_cache, &definition).await }
     }
 
     /// Construct a changeset and report the endpoint changes to tonic.
     async fn create_changeset(
-        &mut self,
+        &self,
+        state: &ProbeState,
         endpoints: &HashSet<SocketAddr>,
     ) -> Vec<Change<SocketAddr, Endpoint>> {
         let mut changeset = Vec::new();
 
-        let remove_set: HashSet<SocketAddr> = self
-            .endpoints
-            .difference(endpoints)
-            .copied()
-            .into_iter()
-            .collect();
+        let remove_set: HashSet<SocketAddr> =
+            state.endpoints.difference(endpoints).copied().collect();
 
-        let add_set: HashSet<SocketAddr> = endpoints
-            .difference(&self.endpoints)
-            .copied()
-            .into_iter()
-            .collect();
+        let add_set: Vec<SocketAddr> = endpoints.difference(&state.endpoints).copied().collect();
+        let add_set = self
+            .endpoint_sort_strategy
+            .sort(add_set, self.probe_interval, &self.connection_backlog)
+            .await;
 
         changeset.extend(
             add_set
@@ -142,8 +707,8 @@ impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
     }
 
     /// Update the endpoint working set to be equal to the result of the last probe.
-    fn overwrite_endpoints(&mut self, current_ips: HashSet<SocketAddr>) {
-        self.endpoints = current_ips;
+    fn overwrite_endpoints(state: &mut ProbeState, current_ips: HashSet<SocketAddr>) {
+        state.endpoints = current_ips;
     }
 
     /// Report `changeset` to the gRPC client and commit the changes
@@ -151,40 +716,116 @@ impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
     ///
     /// Function fails if the `Sender` is closed.
     #[tracing::instrument(
-        skip(endpoints, self),
+        skip(endpoints, self, state),
         level = "debug",
         name = "report-and-commit-endpoint-changeset"
     )]
     async fn report_and_commit(
-        &mut self,
+        &self,
+        state: &mut ProbeState,
         changeset: Vec<Change<SocketAddr, Endpoint>>,
         endpoints: HashSet<SocketAddr>,
     ) -> Result<(), anyhow::Error> {
+        let now = Instant::now();
         for change in changeset {
+            let event = match change {
+                Change::Insert(addr, _) => {
+                    state.submitted_at.insert(addr, now);
+                    EndpointChangeEvent::Added(addr)
+                }
+                Change::Remove(addr) => {
+                    state.submitted_at.remove(&addr);
+                    EndpointChangeEvent::Removed(addr)
+                }
+            };
+
             if self.endpoint_reporter.send(change).await.is_err() {
                 return Err(anyhow::anyhow!("Tried to report endpoint changes on a closed channel, this is probably due to the gRPC client being dropped."));
             }
+
+            let (hook, addr) = match event {
+                EndpointChangeEvent::Added(addr) => (self.endpoint_added_hook, addr),
+                EndpointChangeEvent::Removed(addr) => (self.endpoint_removed_hook, addr),
+            };
+            if let Some(hook) = hook {
+                hook(addr);
+            }
+
+            if let Some(ref publisher) = self.endpoint_change_publisher {
+                // Sending only fails if there are no subscribers, which is not an error for us.
+                let _ = publisher.send(event);
+            }
         }
 
         // When we reach this point we have sent all the changes to the client
         // and can overwrite the endpoints.
         // If we failed earlier the client died so we're in the clear!
-        self.overwrite_endpoints(endpoints);
+        Self::overwrite_endpoints(state, endpoints);
+        if !state.endpoints.is_empty() {
+            let callback = self
+                .channel_ready_callback
+                .lock()
+                .expect("channel ready callback lock poisoned")
+                .take();
+            if let Some(callback) = callback {
+                callback();
+            }
+        }
+        if let Some(ref peer_count) = self.peer_count {
+            peer_count.store(state.endpoints.len(), Ordering::Relaxed);
+        }
+
+        self.reconnect_aged_endpoints(state).await
+    }
+
+    /// Submit the endpoints again that were submitted longer than `reconnect_after` ago,
+    /// so that tonic replaces their connection before the server closes it.
+    async fn reconnect_aged_endpoints(&self, state: &mut ProbeState) -> Result<(), anyhow::Error> {
+        let reconnect_after = match self.reconnect_after {
+            Some(reconnect_after) => reconnect_after,
+            None => return Ok(()),
+        };
+
+        let now = Instant::now();
+        let aged: Vec<SocketAddr> = state
+            .submitted_at
+            .iter()
+            .filter(|(_, submitted_at)| now.duration_since(**submitted_at) >= reconnect_after)
+            .map(|(addr, _)| *addr)
+            .collect();
+
+        for addr in aged {
+            state.submitted_at.insert(addr, now);
+            let endpoint = match self.build_endpoint(&addr) {
+                Some(endpoint) => endpoint,
+                None => continue,
+            };
+
+            if self.logs_at(Level::DEBUG) {
+                tracing::debug!("replacing the connection to endpoint {}", addr);
+            }
+            if self
+                .endpoint_reporter
+                .send(Change::Insert(addr, endpoint))
+                .await
+                .is_err()
+            {
+                return Err(anyhow::anyhow!("Tried to report endpoint changes on a closed channel, this is probably due to the gRPC client being dropped."));
+            }
+        }
 
         Ok(())
     }
 
     fn build_endpoint(&self, ip_address: &SocketAddr) -> Option<Endpoint> {
-        let uri = format!(
-            "{}://{}:{}",
-            self.scheme,
-            ip_address.ip().to_string(),
-            ip_address.port()
-        );
+        // Formatting the `SocketAddr` wraps IPv6 addresses in brackets, as URIs require.
+        let uri = format!("{}://{}", self.scheme, ip_address);
 
         let mut endpoint = Endpoint::from_shared(uri)
             .map_err(|err| {
-                tracing::warn!("endpoint creation error: {:?}", err);
+                if self.logs_at(Level::WARN) {
+                    tracing::warn!("endpoint creation error: {:?}", err);
+                }
             })
             .ok()?;
 
@@ -192,7 +833,9 @@ impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
             endpoint = endpoint
                 .tls_config(tls_config.clone())
                 .map_err(|err| {
-                    tracing::warn!("tls error: {:?}", err);
+                    if self.logs_at(Level::WARN) {
+                        tracing::warn!("tls error: {:?}", err);
+                    }
                     err
                 })
                 .ok()?;
@@ -205,3 +848,81 @@ impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
         Some(endpoint)
     }
 }
+
+/// The index of `level` in `LOG_LEVELS`.
+fn log_level_index(level: Level) -> u8 {
+    LOG_LEVELS
+        .iter()
+        .position(|known| *known == level)
+        .unwrap_or(LOG_LEVELS.len() - 1) as u8
+}
+
+/// Only emit the events up to `level` from now on, see [`GrpcServiceProbe::set_log_level`].
+pub(crate) fn store_log_level(log_level: &AtomicU8, level: Level) {
+    log_level.store(log_level_index(level), Ordering::Relaxed);
+}
+
+/// Whether events at `level` are emitted with the `log_level` index.
+fn logs_at(log_level: &AtomicU8, level: Level) -> bool {
+    log_level_index(level) <= log_level.load(Ordering::Relaxed)
+}
+
+/// Resolve the endpoints of `definition` with `dns_lookup`, going through `resolver_cache` if there is one.
+/// Shared by [`GrpcServiceProbe`] and [`ResolveService`], so that both agree on what is cached and for how long.
+async fn resolve_through_cache<Lookup: LookupService + Sync>(
+    dns_lookup: Arc<Lookup>,
+    resolver_cache: Option<Arc<SharedResolverCache>>,
+    definition: &ServiceDefinition,
+) -> Result<Resolved, anyhow::Error> {
+    let resolve = async {
+        let (endpoints, expires_at) = dns_lookup
+            .resolve_service_endpoints_with_expiry(definition)
+            .await?;
+        Ok((endpoints.into_iter().collect(), expires_at))
+    };
+
+    match resolver_cache {
+        Some(cache) => {
+            cache
+                .get_or_resolve(&definition.hostname, definition.port, resolve)
+                .await
+        }
+        None => resolve.await,
+    }
+}
+
+/// Wait until another connection may be opened, if the `backlog` is limited.
+pub(crate) async fn connection_permit(
+    backlog: &Option<Arc<Semaphore>>,
+) -> Option<OwnedSemaphorePermit> {
+    match backlog {
+        Some(backlog) => Arc::clone(backlog).acquire_owned().await.ok(),
+        None => None,
+    }
+}
+
+impl<'a, Lookup> Service<&'a str> for GrpcServiceProbe<Lookup>
+where
+    Lookup: LookupService + Send + Sync + 'static,
+{
+    type Response = Vec<SocketAddr>;
+    type Error = anyhow::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, hostname: &'a str) -> Self::Future {
+        let definition = ServiceDefinition {
+            hostname: hostname.to_string(),
+            port: self.service_definition.port,
+        };
+
+        let resolve = self.resolve(definition);
+        Box::pin(async move {
+            let (endpoints, _) = resolve.await?;
+            Ok(endpoints)
+        })
+    }
+}
@@ -0,0 +1,43 @@
+//! OpenTelemetry spans for the probe cycles of [`GrpcServiceProbe`](crate::service_probe::GrpcServiceProbe).
+
+use crate::ServiceDefinition;
+use opentelemetry::global::{BoxedSpan, BoxedTracer};
+use opentelemetry::trace::{Span, Status, Tracer};
+use opentelemetry::KeyValue;
+
+/// The span covering a single probe cycle.
+pub(crate) struct ProbeSpan(BoxedSpan);
+
+impl ProbeSpan {
+    /// Start a `ginepro.probe` span for a probe cycle of `service_definition`.
+    pub(crate) fn start(tracer: &BoxedTracer, service_definition: &ServiceDefinition) -> Self {
+        let span = tracer
+            .span_builder("ginepro.probe")
+            .with_attributes(vec![
+                KeyValue::new("service.name", service_definition.hostname.clone()),
+                KeyValue::new("net.peer.name", service_definition.hostname.clone()),
+                KeyValue::new("net.peer.port", i64::from(service_definition.port)),
+            ])
+            .start(tracer);
+
+        Self(span)
+    }
+
+    /// Record the number of endpoints that have been resolved.
+    pub(crate) fn record_endpoint_count(&mut self, count: usize) {
+        self.0
+            .set_attribute(KeyValue::new("endpoint.count", count as i64));
+    }
+
+    /// Mark the probe cycle as failed.
+    pub(crate) fn record_error(&mut self, err: &anyhow::Error) {
+        self.0
+            .set_attribute(KeyValue::new("error", err.to_string()));
+        self.0.set_status(Status::error(err.to_string()));
+    }
+
+    /// End the span once the probe cycle has completed.
+    pub(crate) fn end(mut self) {
+        self.0.end();
+    }
+}
@@ -0,0 +1,93 @@
+//! A tower [`Service`] calling a single unary gRPC method on a [`LoadBalancedChannel`].
+
+use crate::LoadBalancedChannel;
+use http::uri::PathAndQuery;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tonic::client::Grpc;
+use tonic::codec::ProstCodec;
+use tower::Service;
+
+/// Implements [`Service`] for [`tonic::Request`]s of a single unary gRPC method,
+/// encoding the request message `M` and decoding the response message `R` with prost.
+///
+/// This allows middleware working on `tonic` requests and responses to be applied to a
+/// [`LoadBalancedChannel`] without a generated client.
+///
+/// ```rust
+/// #[tokio::main]
+/// async fn main() {
+///     use ginepro::LoadBalancedChannel;
+///     use shared_proto::pb::{Ping, Pong};
+///
+///     let load_balanced_channel = LoadBalancedChannel::builder(("my_hostname", 5000))
+///         .await
+///         .expect("failed to read system conf")
+///         .channel();
+///
+///     let test = load_balanced_channel.unary_service::<Ping, Pong>("/test.Tester/Test");
+/// }
+/// ```
+pub struct UnaryService<M, R> {
+    grpc: Grpc<LoadBalancedChannel>,
+    path: PathAndQuery,
+    messages: PhantomData<fn(M) -> R>,
+}
+
+impl<M, R> Clone for UnaryService<M, R> {
+    fn clone(&self) -> Self {
+        Self {
+            grpc: self.grpc.clone(),
+            path: self.path.clone(),
+            messages: PhantomData,
+        }
+    }
+}
+
+impl LoadBalancedChannel {
+    /// Construct a [`UnaryService`] for the unary gRPC method at `path`,
+    /// e.g. `/my.package.MyService/MyMethod`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` is not a valid URI path.
+    pub fn unary_service<M, R>(&self, path: &'static str) -> UnaryService<M, R> {
+        UnaryService {
+            grpc: Grpc::new(self.clone()),
+            path: PathAndQuery::from_static(path),
+            messages: PhantomData,
+        }
+    }
+}
+
+impl<M, R> Service<tonic::Request<M>> for UnaryService<M, R>
+where
+    M: prost::Message + Send + Sync + 'static,
+    R: prost::Message + Default + Send + Sync + 'static,
+{
+    type Response = tonic::Response<R>;
+    type Error = tonic::Status;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    /// Always ready, like a generated tonic client the channel is awaited within the call.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: tonic::Request<M>) -> Self::Future {
+        let mut grpc = self.grpc.clone();
+        let path = self.path.clone();
+
+        Box::pin(async move {
+            grpc.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e),
+                )
+            })?;
+            grpc.unary(request, path, ProstCodec::default()).await
+        })
+    }
+}
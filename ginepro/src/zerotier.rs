@@ -0,0 +1,91 @@
+//! Implements [`LookupService`] for the members of a ZeroTier network.
+
+use crate::{LookupService, ServiceDefinition};
+use anyhow::Context;
+use hyper::client::HttpConnector;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+
+// The address of the API of the ZeroTier service running on the local machine.
+static DEFAULT_API_URL: &str = "http://localhost:9993";
+
+/// Implements [`LookupService`] by resolving [`ServiceDefinition::hostname`] to the
+/// assigned addresses of the ZeroTier network members with that name.
+///
+/// Members are listed with `GET /network/{id}/member` on the ZeroTier API, authenticated
+/// with the API token. A hostname that no member is named after resolves to no endpoints.
+pub struct ZeroTierLookupService {
+    client: hyper::Client<HttpConnector>,
+    members_uri: http::Uri,
+    token: String,
+}
+
+impl ZeroTierLookupService {
+    /// Construct a [`ZeroTierLookupService`] for the members of `network_id`,
+    /// using the API of the local ZeroTier service.
+    pub fn new(network_id: &str, token: impl Into<String>) -> Result<Self, anyhow::Error> {
+        Self::with_api_url(DEFAULT_API_URL, network_id, token)
+    }
+
+    /// Construct a [`ZeroTierLookupService`] for the members of `network_id`,
+    /// using the ZeroTier API at `api_url`, e.g. `http://localhost:9993`.
+    pub fn with_api_url(
+        api_url: &str,
+        network_id: &str,
+        token: impl Into<String>,
+    ) -> Result<Self, anyhow::Error> {
+        let members_uri = format!(
+            "{}/network/{}/member",
+            api_url.trim_end_matches('/'),
+            network_id
+        )
+        .parse()
+        .context("invalid ZeroTier API url")?;
+
+        Ok(Self {
+            client: hyper::Client::new(),
+            members_uri,
+            token: token.into(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl LookupService for ZeroTierLookupService {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let request = http::Request::get(self.members_uri.clone())
+            .header("X-ZT1-Auth", &self.token)
+            .body(hyper::Body::empty())
+            .context("failed to build ZeroTier API request")?;
+
+        let response = self
+            .client
+            .request(request)
+            .await
+            .context("failed to list ZeroTier network members")?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "listing ZeroTier network members failed with status {}",
+                response.status()
+            ));
+        }
+
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .context("failed to read ZeroTier network members")?;
+        let members: Vec<serde_json::Value> =
+            serde_json::from_slice(&body).context("invalid ZeroTier network members")?;
+
+        Ok(members
+            .iter()
+            .filter(|member| member["name"].as_str() == Some(definition.hostname.as_str()))
+            .filter_map(|member| member["config"]["ipAssignments"].as_array())
+            .flatten()
+            .filter_map(|address| address.as_str()?.parse::<IpAddr>().ok())
+            .map(|address| SocketAddr::new(address, definition.port))
+            .collect())
+    }
+}
@@ -124,3 +124,23 @@ impl ServerCertVerifier for NoVerifier {
         Ok(ServerCertVerified::assertion())
     }
 }
+
+/// Accepts every certificate like [`NoVerifier`], but records the names they were verified against.
+#[derive(Default)]
+pub struct RecordingVerifier {
+    pub dns_names: std::sync::Mutex<Vec<String>>,
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        _presented_certs: &[rustls::Certificate],
+        dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let dns_name: &str = dns_name.into();
+        self.dns_names.lock().unwrap().push(dns_name.to_string());
+        Ok(ServerCertVerified::assertion())
+    }
+}
@@ -0,0 +1,9 @@
+//! Compile-time checks that the public types keep the auto traits users rely on.
+//! A refactor that breaks one of these fails to compile the test suite.
+
+use ginepro::{DnsResolver, GrpcServiceProbe, LoadBalancedChannel, LoadBalancedChannelBuilder};
+use static_assertions::assert_impl_all;
+
+assert_impl_all!(LoadBalancedChannel: Send, Sync, Clone);
+assert_impl_all!(LoadBalancedChannelBuilder<DnsResolver>: Send);
+assert_impl_all!(GrpcServiceProbe<DnsResolver>: Send, Sync, Clone);
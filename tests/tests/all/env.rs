@@ -0,0 +1,104 @@
+use ginepro::{GineproError, LoadBalancedChannelBuilder};
+use std::error::Error;
+
+// Every test uses its own variables since tests run concurrently in the same process.
+
+#[tokio::test]
+async fn durations_are_read_from_the_environment() {
+    std::env::set_var("GINEPRO_TEST_TIMEOUT_MS", "250");
+    std::env::set_var("GINEPRO_TEST_PROBE_INTERVAL_MS", " 1000 ");
+
+    let builder = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .with_timeout_from_env("GINEPRO_TEST_TIMEOUT_MS")
+        .and_then(|builder| builder.with_probe_interval_from_env("GINEPRO_TEST_PROBE_INTERVAL_MS"))
+        .expect("valid durations were rejected");
+    let explanation = builder.explain();
+    let lines: Vec<&str> = explanation.lines().collect();
+
+    assert!(lines.contains(&"timeout: 250ms (set)"));
+    assert!(lines.contains(&"probe interval: 1s (set)"));
+}
+
+#[tokio::test]
+async fn unset_variables_are_ignored() {
+    std::env::remove_var("GINEPRO_TEST_UNSET_MS");
+
+    let builder = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .with_timeout_from_env("GINEPRO_TEST_UNSET_MS")
+        .and_then(|builder| builder.with_probe_interval_from_env("GINEPRO_TEST_UNSET_MS"))
+        .expect("unset variables were rejected");
+    let explanation = builder.explain();
+    let lines: Vec<&str> = explanation.lines().collect();
+
+    assert!(lines.contains(&"timeout: none (default)"));
+    assert!(lines.contains(&"probe interval: 10s (default)"));
+}
+
+#[tokio::test]
+async fn invalid_durations_are_rejected() {
+    std::env::set_var("GINEPRO_TEST_INVALID_MS", "10s");
+
+    let err = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .with_timeout_from_env("GINEPRO_TEST_INVALID_MS")
+        .err()
+        .expect("an invalid timeout was accepted");
+
+    match err {
+        GineproError::InvalidEnvVar {
+            ref var, ref value, ..
+        } => {
+            assert_eq!(var, "GINEPRO_TEST_INVALID_MS");
+            assert_eq!(value, "10s");
+        }
+        _ => panic!("unexpected error {:?}", err),
+    }
+    assert!(err.source().is_some());
+}
+
+#[tokio::test]
+async fn invalid_durations_keep_the_parse_error_as_their_source() {
+    std::env::set_var("GINEPRO_TEST_CHAIN_MS", "-1");
+
+    let err = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .with_probe_interval_from_env("GINEPRO_TEST_CHAIN_MS")
+        .err()
+        .expect("an invalid probe interval was accepted");
+
+    let err = anyhow::Error::from(err);
+    let chain: Vec<_> = err.chain().collect();
+    assert_eq!(chain.len(), 2);
+    assert!(chain[0].is::<GineproError>());
+    assert!(chain[1].is::<std::num::ParseIntError>());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn non_unicode_values_keep_the_var_error_as_their_source() {
+    use std::os::unix::ffi::OsStrExt;
+
+    std::env::set_var(
+        "GINEPRO_TEST_NON_UNICODE_MS",
+        std::ffi::OsStr::from_bytes(b"10\xff"),
+    );
+
+    let err = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .with_timeout_from_env("GINEPRO_TEST_NON_UNICODE_MS")
+        .err()
+        .expect("a non unicode timeout was accepted");
+
+    let source = err.source().expect("the error has no source");
+    assert!(matches!(
+        source.downcast_ref::<std::env::VarError>(),
+        Some(std::env::VarError::NotUnicode(_))
+    ));
+}
@@ -0,0 +1,41 @@
+use crate::lookup::TestDnsResolver;
+use ginepro::{EndpointSortStrategy, LoadBalancedChannelBuilder};
+use tokio::time::Duration;
+
+#[tokio::test]
+async fn explain_marks_options_set_explicitly() {
+    let explanation = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .dns_probe_interval(Duration::from_secs(3))
+        .with_no_delay_on_idle(true)
+        .explain();
+    let lines: Vec<&str> = explanation.lines().collect();
+
+    assert!(lines.contains(&"service: test:5000"));
+    assert!(lines.contains(&"lookup service: dns (default)"));
+    assert!(lines.contains(&"probe interval: 3s (set)"));
+    assert!(lines.contains(&"no delay on idle: enabled (set)"));
+    assert!(lines.contains(&"timeout: none (default)"));
+    assert!(lines.contains(&"tls: disabled (default)"));
+    assert!(lines.contains(&"endpoint ttl aware removal: disabled (default)"));
+}
+
+#[tokio::test]
+async fn options_set_to_their_default_are_marked_as_set() {
+    let explanation = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(TestDnsResolver::default())
+        .dns_probe_interval(Duration::from_secs(10))
+        .with_no_delay_on_idle(false)
+        .with_endpoint_sort_strategy(EndpointSortStrategy::None)
+        .explain();
+    let lines: Vec<&str> = explanation.lines().collect();
+
+    assert!(lines.contains(&"lookup service: custom (set)"));
+    assert!(lines.contains(&"probe interval: 10s (set)"));
+    assert!(lines.contains(&"no delay on idle: disabled (set)"));
+    assert!(lines.contains(&"endpoint sort strategy: None (set)"));
+    assert!(lines.contains(&"endpoint ttl aware removal: disabled (default)"));
+}
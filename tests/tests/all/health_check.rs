@@ -0,0 +1,91 @@
+use crate::lookup::{ExpiringDnsResolver, TestDnsResolver};
+use ginepro::{GrpcHealthCheckService, LookupService, ServiceDefinition};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use tests::test_server::TestServer;
+use tonic_health::ServingStatus;
+
+/// Start a server that reports `status` for the service `my.Service`.
+async fn start_health_server(status: ServingStatus) -> TestServer {
+    let (mut reporter, server) = tonic_health::server::health_reporter();
+    reporter.set_service_status("my.Service", status).await;
+
+    TestServer::start(server, None, None).await
+}
+
+#[tokio::test]
+async fn only_serving_endpoints_are_resolved() {
+    let serving = start_health_server(ServingStatus::Serving).await;
+    let not_serving = start_health_server(ServingStatus::NotServing).await;
+    let unreachable = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("failed to bind listener");
+    let unreachable_address = unreachable.local_addr().unwrap().to_string();
+    drop(unreachable);
+
+    let mut resolver = TestDnsResolver::default();
+    for address in &[
+        serving.address().to_string(),
+        not_serving.address().to_string(),
+        unreachable_address,
+    ] {
+        resolver
+            .add_ip_without_server(address.clone(), address.clone())
+            .await;
+    }
+    let lookup = GrpcHealthCheckService::new(resolver, "my.Service");
+
+    let endpoints = lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("test", 5000)))
+        .await
+        .expect("lookup failed");
+
+    let expected: HashSet<SocketAddr> = [serving.address().parse().unwrap()]
+        .iter()
+        .copied()
+        .collect();
+    assert_eq!(endpoints, expected);
+}
+
+#[tokio::test]
+async fn endpoints_without_the_checked_service_are_not_resolved() {
+    let server = start_health_server(ServingStatus::Serving).await;
+
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server".to_string(), server.address().to_string())
+        .await;
+    let lookup = GrpcHealthCheckService::new(resolver, "other.Service");
+
+    let endpoints = lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("test", 5000)))
+        .await
+        .expect("lookup failed");
+
+    assert!(endpoints.is_empty());
+}
+
+#[tokio::test]
+async fn the_expiry_of_the_inner_lookup_is_forwarded() {
+    let server = start_health_server(ServingStatus::Serving).await;
+
+    let mut inner = TestDnsResolver::default();
+    inner
+        .add_ip_without_server("server".to_string(), server.address().to_string())
+        .await;
+    let lookup = GrpcHealthCheckService::new(
+        ExpiringDnsResolver {
+            inner,
+            ttl: tokio::time::Duration::from_secs(30),
+        },
+        "my.Service",
+    );
+
+    let (endpoints, expires_at) = lookup
+        .resolve_service_endpoints_with_expiry(&ServiceDefinition::from(("test", 5000)))
+        .await
+        .expect("lookup failed");
+
+    assert_eq!(endpoints.len(), 1);
+    assert!(expires_at.is_some());
+}
@@ -5,6 +5,7 @@ use shared_proto::pb::tester_server::TesterServer;
 use shared_proto::pb::{Ping, Pong};
 use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tests::test_server::TestServer;
 use tokio::sync::{Mutex, RwLock};
@@ -34,6 +35,24 @@ impl Tester for TesterImpl {
     }
 }
 
+/// A [`Tester`] that takes `delay` to answer its first call and answers all later calls right away.
+#[derive(Clone)]
+pub struct SlowFirstCallTesterImpl {
+    pub inner: TesterImpl,
+    pub calls: Arc<AtomicUsize>,
+    pub delay: tokio::time::Duration,
+}
+
+#[async_trait::async_trait]
+impl Tester for SlowFirstCallTesterImpl {
+    async fn test(&self, req: tonic::Request<Ping>) -> Result<tonic::Response<Pong>, Status> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            tokio::time::sleep(self.delay).await;
+        }
+        self.inner.test(req).await
+    }
+}
+
 #[derive(Clone)]
 pub struct TestDnsResolver {
     pub ips: Arc<RwLock<HashMap<String, String>>>,
@@ -109,9 +128,127 @@ impl LookupService for TestDnsResolver {
 
         Ok(ips
             .values()
-            .cloned()
-            .into_iter()
             .map(|address| address.parse().expect("not a valid ip address"))
             .collect())
     }
 }
+
+/// Wraps a [`TestDnsResolver`] and counts how often it has been queried.
+/// Every lookup takes `delay` to answer, and its endpoints expire after `ttl` if set.
+#[derive(Clone, Default)]
+pub struct CountingDnsResolver {
+    pub inner: TestDnsResolver,
+    pub lookups: Arc<AtomicUsize>,
+    pub delay: tokio::time::Duration,
+    pub ttl: Option<tokio::time::Duration>,
+}
+
+impl CountingDnsResolver {
+    pub fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait::async_trait]
+impl LookupService for CountingDnsResolver {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        self.lookups.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.inner.resolve_service_endpoints(definition).await
+    }
+
+    async fn resolve_service_endpoints_with_expiry(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<(HashSet<SocketAddr>, Option<std::time::Instant>), anyhow::Error> {
+        let endpoints = self.resolve_service_endpoints(definition).await?;
+        Ok((
+            endpoints,
+            self.ttl.map(|ttl| std::time::Instant::now() + ttl),
+        ))
+    }
+}
+
+/// A [`LookupService`] that takes `delay` to answer every lookup.
+pub struct SlowDnsResolver {
+    pub inner: TestDnsResolver,
+    pub delay: tokio::time::Duration,
+}
+
+#[async_trait::async_trait]
+impl LookupService for SlowDnsResolver {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        tokio::time::sleep(self.delay).await;
+        self.inner.resolve_service_endpoints(definition).await
+    }
+}
+
+/// A [`LookupService`] whose lookups always fail.
+pub struct FailingDnsResolver;
+
+#[async_trait::async_trait]
+impl LookupService for FailingDnsResolver {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        Err(anyhow::anyhow!("lookup failed"))
+    }
+}
+
+/// Wraps a [`TestDnsResolver`] and reports that its endpoints expire `ttl` after every lookup.
+#[derive(Clone, Default)]
+pub struct ExpiringDnsResolver {
+    pub inner: TestDnsResolver,
+    pub ttl: tokio::time::Duration,
+}
+
+#[async_trait::async_trait]
+impl LookupService for ExpiringDnsResolver {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        self.inner.resolve_service_endpoints(definition).await
+    }
+
+    async fn resolve_service_endpoints_with_expiry(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<(HashSet<SocketAddr>, Option<std::time::Instant>), anyhow::Error> {
+        let endpoints = self.inner.resolve_service_endpoints(definition).await?;
+        Ok((endpoints, Some(std::time::Instant::now() + self.ttl)))
+    }
+}
+
+/// Wraps a [`TestDnsResolver`] and fails its first `failures` lookups.
+#[derive(Clone, Default)]
+pub struct FlakyDnsResolver {
+    pub inner: TestDnsResolver,
+    pub failures: Arc<AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl LookupService for FlakyDnsResolver {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let failed = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+                failures.checked_sub(1)
+            })
+            .is_ok();
+        if failed {
+            return Err(anyhow::anyhow!("lookup failed"));
+        }
+        self.inner.resolve_service_endpoints(definition).await
+    }
+}
@@ -0,0 +1,63 @@
+use crate::lookup::{FlakyDnsResolver, SlowDnsResolver, TestDnsResolver};
+use ginepro::{LookupService, RetryingLookupService, ServiceDefinition, TimeoutLookupService};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::time::Duration;
+
+#[tokio::test]
+async fn slow_lookups_time_out() {
+    let lookup = TimeoutLookupService::new(
+        SlowDnsResolver {
+            inner: TestDnsResolver::default(),
+            delay: Duration::from_secs(5),
+        },
+        Duration::from_millis(10),
+    );
+
+    let result = lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("test", 5000)))
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn failed_lookups_are_retried() {
+    let mut inner = TestDnsResolver::default();
+    inner
+        .add_ip_without_server("server".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+    let failures = Arc::new(AtomicUsize::new(2));
+    let lookup = RetryingLookupService::new(
+        FlakyDnsResolver {
+            inner,
+            failures: Arc::clone(&failures),
+        },
+        2,
+    );
+
+    let endpoints = lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("test", 5000)))
+        .await
+        .expect("lookup was not retried");
+
+    assert_eq!(endpoints.len(), 1);
+    assert_eq!(failures.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn lookups_fail_once_the_retries_are_used_up() {
+    let lookup = RetryingLookupService::new(
+        FlakyDnsResolver {
+            inner: TestDnsResolver::default(),
+            failures: Arc::new(AtomicUsize::new(3)),
+        },
+        2,
+    );
+
+    let result = lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("test", 5000)))
+        .await;
+
+    assert!(result.is_err());
+}
@@ -1,2 +1,19 @@
+mod compile_tests;
+mod env;
+mod explain;
+mod grpc_uri;
+#[cfg(feature = "health-check")]
+mod health_check;
 pub mod lookup;
+mod lookup_retry;
+mod nat64;
+mod properties;
+mod request_logging;
+mod resolve_all;
 mod service_probe;
+#[cfg(feature = "opentelemetry")]
+mod telemetry;
+#[cfg(feature = "url")]
+mod url_conversion;
+#[cfg(feature = "zerotier")]
+mod zerotier;
@@ -0,0 +1,84 @@
+use crate::lookup::{ExpiringDnsResolver, TestDnsResolver};
+use ginepro::{LookupService, Nat64LookupService, ServiceDefinition};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+
+#[tokio::test]
+async fn ipv4_endpoints_are_synthesized_with_the_nat64_prefix() {
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("v4".to_string(), "192.0.2.33:5000".to_string())
+        .await;
+    resolver
+        .add_ip_without_server("v6".to_string(), "[2001:db8::1]:5000".to_string())
+        .await;
+    let lookup = Nat64LookupService::new(resolver, "2001:db8:64::".parse().unwrap());
+
+    let endpoints = lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("test", 5000)))
+        .await
+        .expect("lookup failed");
+
+    let expected: HashSet<SocketAddr> = ["[2001:db8:64::c000:221]:5000", "[2001:db8::1]:5000"]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect();
+    assert_eq!(endpoints, expected);
+}
+
+#[tokio::test]
+async fn ipv4_endpoints_can_be_kept() {
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("v4".to_string(), "192.0.2.33:5000".to_string())
+        .await;
+    let lookup = Nat64LookupService::new(resolver, "2001:db8:64::".parse().unwrap())
+        .with_ipv4_endpoints(true);
+
+    let endpoints = lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("test", 5000)))
+        .await
+        .expect("lookup failed");
+
+    let expected: HashSet<SocketAddr> = ["192.0.2.33:5000", "[2001:db8:64::c000:221]:5000"]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect();
+    assert_eq!(endpoints, expected);
+}
+
+#[tokio::test]
+async fn the_well_known_prefix_is_used_by_default() {
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("v4".to_string(), "192.0.2.33:5000".to_string())
+        .await;
+    let lookup = Nat64LookupService::with_well_known_prefix(resolver);
+
+    let endpoints = lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("test", 5000)))
+        .await
+        .expect("lookup failed");
+
+    assert!(endpoints.contains(&"[64:ff9b::c000:221]:5000".parse().unwrap()));
+}
+
+#[tokio::test]
+async fn the_expiry_of_the_inner_lookup_is_forwarded() {
+    let mut inner = TestDnsResolver::default();
+    inner
+        .add_ip_without_server("v4".to_string(), "192.0.2.33:5000".to_string())
+        .await;
+    let lookup = Nat64LookupService::with_well_known_prefix(ExpiringDnsResolver {
+        inner,
+        ttl: tokio::time::Duration::from_secs(30),
+    });
+
+    let (endpoints, expires_at) = lookup
+        .resolve_service_endpoints_with_expiry(&ServiceDefinition::from(("test", 5000)))
+        .await
+        .expect("lookup failed");
+
+    assert!(endpoints.contains(&"[64:ff9b::c000:221]:5000".parse().unwrap()));
+    assert!(expires_at.is_some());
+}
@@ -0,0 +1,127 @@
+//! Property-based tests for service definitions and the endpoint changes derived from lookups.
+//!
+//! Set `PROPTEST_RNG_SEED` to reproduce a run; more cases are generated in release builds.
+//!
+//! `ServiceDefinition` can only be constructed from a `(hostname, port)` tuple, so there is no
+//! invalid input to reject yet, and lookups are not filtered by address family: every IPv4 and
+//! IPv6 endpoint that is resolved has to be reported.
+
+use crate::lookup::TestDnsResolver;
+use ginepro::{
+    EndpointChangeEvent, EndpointSortStrategy, LoadBalancedChannelBuilder, ServiceDefinition,
+};
+use proptest::prelude::*;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use tokio::sync::broadcast;
+
+fn config() -> ProptestConfig {
+    ProptestConfig::with_cases(if cfg!(debug_assertions) { 32 } else { 512 })
+}
+
+fn endpoints() -> impl Strategy<Value = HashSet<SocketAddr>> {
+    prop::collection::hash_set(
+        (any::<IpAddr>(), any::<u16>()).prop_map(SocketAddr::from),
+        0..16,
+    )
+}
+
+/// Replace the endpoints returned by `resolver` at once, so that a probe never sees a partial update.
+async fn set_endpoints(resolver: &TestDnsResolver, endpoints: &HashSet<SocketAddr>) {
+    let ips: HashMap<String, String> = endpoints
+        .iter()
+        .map(|endpoint| (endpoint.to_string(), endpoint.to_string()))
+        .collect();
+
+    *resolver.ips.write().await = ips;
+}
+
+async fn next_events(
+    changes: &mut broadcast::Receiver<EndpointChangeEvent>,
+    count: usize,
+) -> Vec<EndpointChangeEvent> {
+    let mut events = Vec::with_capacity(count);
+    for _ in 0..count {
+        let event = tokio::time::timeout(tokio::time::Duration::from_secs(5), changes.recv())
+            .await
+            .expect("timed out waiting for an endpoint change")
+            .expect("no change published");
+        events.push(event);
+    }
+    events
+}
+
+fn added(events: &[EndpointChangeEvent]) -> Vec<SocketAddr> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            EndpointChangeEvent::Added(addr) => Some(*addr),
+            EndpointChangeEvent::Removed(_) => None,
+        })
+        .collect()
+}
+
+fn removed(events: &[EndpointChangeEvent]) -> HashSet<SocketAddr> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            EndpointChangeEvent::Removed(addr) => Some(*addr),
+            EndpointChangeEvent::Added(_) => None,
+        })
+        .collect()
+}
+
+proptest! {
+    #![proptest_config(config())]
+
+    #[test]
+    fn service_definition_keeps_hostname_and_port(hostname in "[a-z0-9.-]{1,64}", port in any::<u16>()) {
+        let definition = ServiceDefinition::from((hostname.as_str(), port));
+
+        prop_assert_eq!(definition.hostname, hostname);
+        prop_assert_eq!(definition.port, port);
+    }
+
+    #[test]
+    fn lookups_are_reported_as_sorted_endpoint_changes(first in endpoints(), second in endpoints()) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build runtime");
+
+        runtime.block_on(async {
+            let resolver = TestDnsResolver::default();
+            set_endpoints(&resolver, &first).await;
+
+            let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+                .await
+                .expect("failed to init")
+                .lookup_service(resolver.clone())
+                .dns_probe_interval(tokio::time::Duration::from_millis(1))
+                .with_endpoint_sort_strategy(EndpointSortStrategy::ByIpAddress)
+                .with_broadcast_endpoint_changes(64);
+            let _load_balanced_channel = builder.channel();
+
+            // Every IPv4 and IPv6 endpoint of the first lookup is added, in order.
+            let events = next_events(&mut changes, first.len()).await;
+            let mut expected: Vec<SocketAddr> = first.iter().copied().collect();
+            expected.sort();
+            prop_assert_eq!(added(&events), expected);
+
+            // Only the difference to the second lookup is reported.
+            set_endpoints(&resolver, &second).await;
+            let events = next_events(
+                &mut changes,
+                first.symmetric_difference(&second).count(),
+            )
+            .await;
+            let mut expected: Vec<SocketAddr> = second.difference(&first).copied().collect();
+            expected.sort();
+            prop_assert_eq!(added(&events), expected);
+            prop_assert_eq!(removed(&events), first.difference(&second).copied().collect());
+
+            Ok(())
+        })?;
+    }
+}
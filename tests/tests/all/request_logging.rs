@@ -0,0 +1,97 @@
+use crate::lookup::{TestDnsResolver, TesterImpl};
+use ginepro::LoadBalancedChannelBuilder;
+use shared_proto::pb::tester_client::TesterClient;
+use shared_proto::pb::Ping;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+pub(crate) type Fields = HashMap<String, String>;
+
+/// A [`Subscriber`] that keeps the fields of every event.
+#[derive(Clone, Default)]
+pub(crate) struct CapturingSubscriber {
+    pub(crate) events: Arc<std::sync::Mutex<Vec<(Level, Fields)>>>,
+}
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl Subscriber for CapturingSubscriber {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        self.events
+            .lock()
+            .unwrap()
+            .push((*event.metadata().level(), fields));
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[tokio::test]
+async fn requests_are_logged_at_the_configured_level() {
+    let subscriber = CapturingSubscriber::default();
+    let _guard = tracing::subscriber::set_default(subscriber.clone());
+
+    let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl(
+            "server".to_string(),
+            TesterImpl {
+                sender: Arc::new(Mutex::new(sender)),
+                name: "server".to_string(),
+            },
+        )
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .with_request_logging(Level::WARN)
+        .channel();
+
+    let mut client = TesterClient::new(load_balanced_channel);
+    tokio::time::timeout(tokio::time::Duration::from_secs(5), client.test(Ping {}))
+        .await
+        .expect("request timed out")
+        .expect("failed to call server");
+
+    let events = subscriber.events.lock().unwrap();
+    let (level, fields) = events
+        .iter()
+        .find(|(_, fields)| fields.get("message").map(String::as_str) == Some("grpc request"))
+        .expect("the request was not logged");
+    assert_eq!(*level, Level::WARN);
+    assert_eq!(fields["path"], "/test.Tester/Test");
+    assert_eq!(fields["authority"], "test:5000");
+    assert_eq!(fields["content_type"], "application/grpc");
+    assert_eq!(fields["status"], "200");
+}
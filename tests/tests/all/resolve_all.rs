@@ -0,0 +1,32 @@
+use crate::lookup::{FailingDnsResolver, TestDnsResolver};
+use ginepro::ServiceDefinition;
+use std::net::SocketAddr;
+
+#[tokio::test]
+async fn all_services_are_resolved() {
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+    let services = vec![
+        ServiceDefinition::from(("first", 5000)),
+        ServiceDefinition::from(("second", 5000)),
+    ];
+
+    let endpoints = ginepro::resolve_all(services.clone(), &resolver).await;
+
+    let expected: Vec<SocketAddr> = vec!["127.0.0.1:5000".parse().unwrap()];
+    assert_eq!(endpoints.len(), 2);
+    for service in &services {
+        assert_eq!(endpoints[service], expected);
+    }
+}
+
+#[tokio::test]
+async fn services_that_fail_to_resolve_have_no_endpoints() {
+    let service = ServiceDefinition::from(("test", 5000));
+
+    let endpoints = ginepro::resolve_all(vec![service.clone()], &FailingDnsResolver).await;
+
+    assert_eq!(endpoints[&service], Vec::<SocketAddr>::new());
+}
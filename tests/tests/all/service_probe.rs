@@ -1,15 +1,30 @@
+use crate::lookup::CountingDnsResolver;
+use crate::lookup::ExpiringDnsResolver;
+use crate::lookup::FailingDnsResolver;
+use crate::lookup::FlakyDnsResolver;
+use crate::lookup::SlowDnsResolver;
+use crate::lookup::SlowFirstCallTesterImpl;
 use crate::lookup::TestDnsResolver;
 use crate::lookup::TesterImpl;
-use ginepro::LoadBalancedChannelBuilder;
+use crate::request_logging::CapturingSubscriber;
+use ginepro::{
+    EndpointChangeEvent, EndpointSortStrategy, GineproError, GrpcServiceProbe,
+    GrpcServiceProbeConfig, LoadBalancedChannelBuilder, LookupService, ResolveService,
+    SharedResolverCache,
+};
 use shared_proto::pb::pong::Payload;
 use shared_proto::pb::tester_client::TesterClient;
-use shared_proto::pb::Ping;
+use shared_proto::pb::{Ping, Pong};
 use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
-use tests::tls::{NoVerifier, TestSslCertificate};
-use tokio::sync::Mutex;
+use tests::tls::{NoVerifier, RecordingVerifier, TestSslCertificate};
+use tokio::sync::{broadcast, Mutex};
 use tonic::transport::ClientTlsConfig;
 use tonic::transport::ServerTlsConfig;
+use tower::discover::Change;
+use tower::{Service, ServiceExt};
 
 fn get_payload_raw(payload: Payload) -> String {
     match payload {
@@ -17,6 +32,42 @@ fn get_payload_raw(payload: Payload) -> String {
     }
 }
 
+/// Wait for the next `count` endpoints to be added.
+async fn next_added_endpoints(
+    changes: &mut broadcast::Receiver<EndpointChangeEvent>,
+    count: usize,
+) -> Vec<SocketAddr> {
+    let mut added = Vec::with_capacity(count);
+    for _ in 0..count {
+        let event = tokio::time::timeout(tokio::time::Duration::from_secs(5), changes.recv())
+            .await
+            .expect("timed out waiting for an endpoint change")
+            .expect("no change published");
+        match event {
+            EndpointChangeEvent::Added(addr) => added.push(addr),
+            EndpointChangeEvent::Removed(addr) => panic!("unexpected removal of {}", addr),
+        }
+    }
+    added
+}
+
+/// A [`GrpcServiceProbeConfig`] for `("test", 5000)` resolved with `lookup`.
+fn probe_config<L: LookupService>(lookup: L) -> GrpcServiceProbeConfig<L> {
+    GrpcServiceProbeConfig {
+        service_definition: ("test", 5000).into(),
+        dns_lookup: lookup,
+        probe_interval: tokio::time::Duration::from_millis(3),
+        endpoint_timeout: None,
+    }
+}
+
+/// Construct a [`GrpcServiceProbe`] for `("test", 5000)` that is not connected to a channel.
+fn detached_probe<L: LookupService + Send + Sync + 'static>(lookup: L) -> GrpcServiceProbe<L> {
+    let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+
+    GrpcServiceProbe::new_with_reporter(probe_config(lookup), sender)
+}
+
 #[tokio::test]
 async fn load_balance_succeeds_with_churn() {
     // Steps:
@@ -41,7 +92,7 @@ async fn load_balance_succeeds_with_churn() {
         .channel();
     let mut client = TesterClient::new(load_balanced_channel);
 
-    let servers: Vec<String> = (0..10).into_iter().map(|s| s.to_string()).collect();
+    let servers: Vec<String> = (0..10).map(|s| s.to_string()).collect();
     let mut servers_called = Vec::new();
 
     // Act
@@ -118,7 +169,7 @@ async fn load_balance_succeeds_with_churn_with_tls_enabled() {
         .channel();
     let mut client = TesterClient::new(load_balanced_channel);
 
-    let servers: Vec<String> = (0..10).into_iter().map(|s| s.to_string()).collect();
+    let servers: Vec<String> = (0..10).map(|s| s.to_string()).collect();
     let mut servers_called = Vec::new();
 
     // Act
@@ -285,3 +336,1088 @@ async fn connection_timeout_is_not_fatal() {
         get_payload_raw(res.into_inner().payload.expect("no payload"))
     );
 }
+
+#[tokio::test]
+async fn endpoint_changes_are_broadcast() {
+    // Scenario:
+    // A server is discovered and later disappears.
+    // We want every subscriber to observe both changes.
+    let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+    let probe_interval = tokio::time::Duration::from_millis(3);
+
+    let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(probe_interval)
+        .with_broadcast_endpoint_changes(16);
+    let mut other_changes = changes.resubscribe();
+    let _load_balanced_channel = builder.channel();
+
+    resolver
+        .add_server_with_provided_impl(
+            "server".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "server".to_string(),
+            },
+        )
+        .await;
+    let address = resolver.ips.read().await["server"]
+        .parse()
+        .expect("not a valid ip address");
+
+    assert_eq!(
+        changes.recv().await.expect("no change published"),
+        EndpointChangeEvent::Added(address)
+    );
+
+    resolver.remove_server("server".to_string()).await;
+
+    assert_eq!(
+        changes.recv().await.expect("no change published"),
+        EndpointChangeEvent::Removed(address)
+    );
+    assert_eq!(
+        other_changes.recv().await.expect("no change published"),
+        EndpointChangeEvent::Added(address)
+    );
+    assert_eq!(
+        other_changes.recv().await.expect("no change published"),
+        EndpointChangeEvent::Removed(address)
+    );
+}
+
+#[tokio::test]
+async fn channels_sharing_a_resolver_cache_deduplicate_lookups() {
+    // Scenario:
+    // Several channels pointing to the same hostname are started at the same time
+    // and share a resolver cache whose entries stay fresh for the whole test.
+    // Lookups are slow, so all probes miss the cache while the first lookup is in flight.
+    // We want only one probe to query the lookup service.
+    let num_channels = 5;
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(num_channels);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = CountingDnsResolver {
+        delay: tokio::time::Duration::from_millis(50),
+        ..Default::default()
+    };
+    let probe_interval = tokio::time::Duration::from_millis(3);
+    let cache = Arc::new(SharedResolverCache::new(
+        16,
+        tokio::time::Duration::from_secs(60),
+    ));
+
+    resolver
+        .inner
+        .add_server_with_provided_impl(
+            "server".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "server".to_string(),
+            },
+        )
+        .await;
+
+    let mut clients = Vec::new();
+    for _ in 0..num_channels {
+        let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+            .await
+            .expect("failed to init")
+            .lookup_service(resolver.clone())
+            .dns_probe_interval(probe_interval)
+            .with_resolver_cache(Arc::clone(&cache))
+            .channel();
+        clients.push(TesterClient::new(load_balanced_channel));
+    }
+
+    // Give time to the slow lookup to complete and the DNS probes to run several times.
+    tokio::time::sleep(resolver.delay * 2 + probe_interval * 10).await;
+
+    for client in &mut clients {
+        client
+            .test(tonic::Request::new(Ping {}))
+            .await
+            .expect("failed to call server");
+        assert_eq!(receiver.recv().await.expect(""), "server");
+    }
+
+    assert_eq!(resolver.lookups(), 1);
+}
+
+#[tokio::test]
+async fn cached_endpoints_expire_with_their_records() {
+    // Scenario:
+    // The records of a service expire long before the TTL of the resolver cache.
+    // We want the service to be resolved again once its records expire.
+    let mut resolver = CountingDnsResolver {
+        ttl: Some(tokio::time::Duration::from_millis(50)),
+        ..Default::default()
+    };
+    resolver
+        .inner
+        .add_ip_without_server("server".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+    let cache = Arc::new(SharedResolverCache::new(
+        16,
+        tokio::time::Duration::from_secs(60),
+    ));
+    let mut service =
+        ResolveService::from(probe_config(resolver.clone())).with_resolver_cache(cache);
+
+    for _ in 0..2 {
+        service
+            .ready()
+            .await
+            .expect("service is not ready")
+            .call("test".to_string())
+            .await
+            .expect("failed to resolve");
+    }
+    assert_eq!(resolver.lookups(), 1);
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(60)).await;
+    service
+        .ready()
+        .await
+        .expect("service is not ready")
+        .call("test".to_string())
+        .await
+        .expect("failed to resolve");
+    assert_eq!(resolver.lookups(), 2);
+}
+
+#[tokio::test]
+async fn endpoints_are_reported_sorted_by_ip_address() {
+    // Scenario:
+    // A single lookup discovers several endpoints at once.
+    // We want them to be reported in ascending address order.
+    let mut resolver = TestDnsResolver::default();
+    let addresses = [
+        "127.0.0.3:5000",
+        "127.0.0.1:5001",
+        "127.0.0.2:4000",
+        "127.0.0.1:5000",
+    ];
+    for address in &addresses {
+        resolver
+            .add_ip_without_server(address.to_string(), address.to_string())
+            .await;
+    }
+
+    let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .with_endpoint_sort_strategy(EndpointSortStrategy::ByIpAddress)
+        .with_broadcast_endpoint_changes(16);
+    let _load_balanced_channel = builder.channel();
+
+    let mut expected: Vec<SocketAddr> = addresses
+        .iter()
+        .map(|address| address.parse().expect("not a valid ip address"))
+        .collect();
+    expected.sort();
+
+    assert_eq!(
+        next_added_endpoints(&mut changes, addresses.len()).await,
+        expected
+    );
+}
+
+#[tokio::test]
+async fn unreachable_endpoints_are_reported_last_when_sorting_by_latency() {
+    // Scenario:
+    // A single lookup discovers an IP without a backing server and an IP with one.
+    // We want the reachable endpoint to be reported first.
+    let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+
+    resolver
+        .add_ip_without_server("ghost_server".into(), "127.0.0.124:5000".into())
+        .await;
+    resolver
+        .add_server_with_provided_impl(
+            "good_server".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "good_server".to_string(),
+            },
+        )
+        .await;
+    let good_server: SocketAddr = resolver.ips.read().await["good_server"]
+        .parse()
+        .expect("not a valid ip address");
+
+    let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(500))
+        .with_endpoint_sort_strategy(EndpointSortStrategy::ByLatency)
+        .with_broadcast_endpoint_changes(16);
+    let _load_balanced_channel = builder.channel();
+
+    assert_eq!(
+        next_added_endpoints(&mut changes, 2).await,
+        vec![good_server, "127.0.0.124:5000".parse().unwrap()]
+    );
+}
+
+#[tokio::test]
+async fn service_probe_can_be_wrapped_in_tower_middleware() {
+    // Scenario:
+    // The probe is used as a tower service behind a timeout.
+    // We want fast lookups to return the endpoints and slow lookups to time out.
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server".into(), "127.0.0.1:5000".into())
+        .await;
+    let timeout = tokio::time::Duration::from_millis(100);
+
+    let mut fast_probe = tower::timeout::Timeout::new(detached_probe(resolver.clone()), timeout);
+    let endpoints = fast_probe
+        .ready()
+        .await
+        .expect("probe is not ready")
+        .call("test")
+        .await
+        .expect("lookup failed");
+    assert_eq!(endpoints, vec!["127.0.0.1:5000".parse().unwrap()]);
+
+    let mut slow_probe = tower::timeout::Timeout::new(
+        detached_probe(SlowDnsResolver {
+            inner: resolver,
+            delay: timeout * 10,
+        }),
+        timeout,
+    );
+    let err = slow_probe
+        .ready()
+        .await
+        .expect("probe is not ready")
+        .call("test")
+        .await
+        .expect_err("lookup should time out");
+    assert!(err.is::<tower::timeout::error::Elapsed>());
+}
+
+#[tokio::test]
+async fn probe_configs_resolve_hostnames_as_a_tower_service() {
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+    let service = ResolveService::from(probe_config(resolver));
+    let mut service = tower::timeout::Timeout::new(service, tokio::time::Duration::from_secs(1));
+
+    let endpoints = service
+        .ready()
+        .await
+        .expect("service is not ready")
+        .call("test".to_string())
+        .await
+        .expect("failed to resolve");
+    assert_eq!(
+        endpoints,
+        vec!["127.0.0.1:5000".parse::<SocketAddr>().unwrap()]
+    );
+
+    let err = ResolveService::from(probe_config(FailingDnsResolver))
+        .oneshot("test".to_string())
+        .await
+        .expect_err("a failed lookup was resolved");
+    assert!(matches!(
+        err,
+        GineproError::LookupFailed { ref hostname, .. } if hostname == "test"
+    ));
+}
+
+#[tokio::test]
+async fn connections_are_replaced_before_the_server_closes_them() {
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+    let probe = GrpcServiceProbe::new_with_reporter(probe_config(resolver), sender)
+        .with_preemptive_reconnect(tokio::time::Duration::from_millis(250));
+    tokio::spawn(probe.probe());
+
+    // The endpoint is submitted once when it is discovered and again to replace its connection.
+    let address: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    for _ in 0..2 {
+        let change = tokio::time::timeout(tokio::time::Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("endpoint was not submitted")
+            .unwrap();
+        match change {
+            Change::Insert(addr, _) => assert_eq!(addr, address),
+            Change::Remove(addr) => panic!("unexpected removal of {}", addr),
+        }
+    }
+}
+
+#[tokio::test]
+#[should_panic(expected = "server max age must not be zero")]
+async fn preemptive_reconnect_rejects_a_zero_server_max_age() {
+    let _builder = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .with_preemptive_reconnect(tokio::time::Duration::ZERO);
+}
+
+/// Wait until `channel` counts `count` peers.
+async fn wait_for_peer_count(channel: &ginepro::LoadBalancedChannel, count: usize) {
+    tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
+        while channel.peer_count() != count {
+            tokio::time::sleep(tokio::time::Duration::from_millis(3)).await;
+        }
+    })
+    .await
+    .expect("peers were not counted");
+}
+
+#[tokio::test]
+async fn peers_are_counted_and_probed_for_readiness() {
+    let mut resolver = TestDnsResolver::default();
+    for (name, address) in &[("first", "127.0.0.1:5000"), ("second", "127.0.0.2:5000")] {
+        resolver
+            .add_ip_without_server(name.to_string(), address.to_string())
+            .await;
+    }
+
+    let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .with_broadcast_endpoint_changes(16);
+    let load_balanced_channel = builder.channel();
+
+    next_added_endpoints(&mut changes, 2).await;
+    wait_for_peer_count(&load_balanced_channel, 2).await;
+    assert!(load_balanced_channel.has_peers());
+    let readiness_probe = load_balanced_channel.readiness_probe();
+    assert!(readiness_probe.clone().oneshot(()).await.is_ok());
+
+    resolver.remove_ip_and_not_server("first".to_string()).await;
+    resolver
+        .remove_ip_and_not_server("second".to_string())
+        .await;
+    wait_for_peer_count(&load_balanced_channel, 0).await;
+    assert!(!load_balanced_channel.has_peers());
+    assert!(matches!(
+        readiness_probe.oneshot(()).await,
+        Err(GineproError::NoEndpointsFound)
+    ));
+}
+
+#[tokio::test]
+async fn lookups_are_retried_by_the_channel() {
+    // Scenario:
+    // The first lookups fail, but fewer times than lookups are retried.
+    // We want the endpoints of the first probe cycle to be reported.
+    let mut inner = TestDnsResolver::default();
+    inner
+        .add_ip_without_server("server".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+    let resolver = FlakyDnsResolver {
+        inner,
+        failures: Arc::new(std::sync::atomic::AtomicUsize::new(2)),
+    };
+
+    let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver)
+        .dns_probe_interval(tokio::time::Duration::from_secs(600))
+        .with_lookup_service_timeout_and_retry(tokio::time::Duration::from_secs(1), 2)
+        .with_broadcast_endpoint_changes(16);
+    let _load_balanced_channel = builder.channel();
+
+    assert_eq!(
+        next_added_endpoints(&mut changes, 1).await,
+        vec!["127.0.0.1:5000".parse::<SocketAddr>().unwrap()]
+    );
+}
+
+#[tokio::test]
+async fn probe_events_are_emitted_up_to_the_log_level() {
+    let subscriber = CapturingSubscriber::default();
+    let _guard = tracing::subscriber::set_default(subscriber.clone());
+    let warnings = || {
+        subscriber
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(level, _)| *level == tracing::Level::WARN)
+            .count()
+    };
+
+    let mut probe = detached_probe(FailingDnsResolver);
+    let handle = probe.clone();
+
+    handle.set_log_level(tracing::Level::ERROR);
+    probe.probe_once().await.expect("probe failed");
+    assert_eq!(warnings(), 0);
+
+    handle.set_log_level(tracing::Level::WARN);
+    probe.probe_once().await.expect("probe failed");
+    assert_eq!(warnings(), 1);
+}
+
+#[tokio::test]
+async fn channels_change_the_log_level_of_their_probe() {
+    let subscriber = CapturingSubscriber::default();
+    let _guard = tracing::subscriber::set_default(subscriber.clone());
+    let warnings = || {
+        subscriber
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(level, _)| *level == tracing::Level::WARN)
+            .count()
+    };
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(FailingDnsResolver)
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .channel();
+
+    load_balanced_channel.set_log_level(tracing::Level::ERROR);
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    assert_eq!(warnings(), 0);
+
+    load_balanced_channel.set_log_level(tracing::Level::WARN);
+    tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
+        while warnings() == 0 {
+            tokio::time::sleep(tokio::time::Duration::from_millis(3)).await;
+        }
+    })
+    .await
+    .expect("the failed lookups were not logged");
+}
+
+#[tokio::test]
+async fn ipv6_endpoints_are_reported() {
+    // Scenario:
+    // A lookup discovers an IPv4 and an IPv6 endpoint.
+    // We want both to be reported to the channel.
+    let mut resolver = TestDnsResolver::default();
+    let addresses = ["127.0.0.1:5000", "[::1]:5000"];
+    for address in &addresses {
+        resolver
+            .add_ip_without_server(address.to_string(), address.to_string())
+            .await;
+    }
+
+    let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .with_endpoint_sort_strategy(EndpointSortStrategy::ByIpAddress)
+        .with_broadcast_endpoint_changes(16);
+    let _load_balanced_channel = builder.channel();
+
+    let expected: Vec<SocketAddr> = addresses
+        .iter()
+        .map(|address| address.parse().expect("not a valid ip address"))
+        .collect();
+
+    assert_eq!(next_added_endpoints(&mut changes, 2).await, expected);
+}
+
+#[tokio::test]
+async fn cloned_probes_share_their_endpoints() {
+    // Scenario:
+    // Two clones of a probe run a probe cycle concurrently.
+    // We want every endpoint to be reported exactly once.
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("a".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+    resolver
+        .add_ip_without_server("b".to_string(), "127.0.0.2:5000".to_string())
+        .await;
+
+    let config = GrpcServiceProbeConfig {
+        service_definition: ("test", 5000).into(),
+        dns_lookup: resolver,
+        probe_interval: tokio::time::Duration::from_millis(3),
+        endpoint_timeout: None,
+    };
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+    let mut probe = GrpcServiceProbe::new_with_reporter(config, sender);
+    let mut clone = probe.clone();
+
+    let (first, second) = tokio::join!(probe.probe_once(), clone.probe_once());
+    first.expect("first probe cycle failed");
+    second.expect("second probe cycle failed");
+    drop((probe, clone));
+
+    let mut reported = Vec::new();
+    while let Some(change) = receiver.recv().await {
+        match change {
+            tower::discover::Change::Insert(addr, _) => reported.push(addr),
+            tower::discover::Change::Remove(addr) => panic!("unexpected removal of {}", addr),
+        }
+    }
+    reported.sort();
+
+    let expected: Vec<SocketAddr> = vec![
+        "127.0.0.1:5000".parse().unwrap(),
+        "127.0.0.2:5000".parse().unwrap(),
+    ];
+    assert_eq!(reported, expected);
+}
+
+#[tokio::test]
+async fn services_without_endpoints_are_probed_without_delay() {
+    // Scenario:
+    // The service has no endpoints when the channel is built and the probe interval is long.
+    // We want endpoints that show up later to be discovered well before the next interval.
+    let mut resolver = TestDnsResolver::default();
+
+    let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_secs(600))
+        .with_no_delay_on_idle(true)
+        .with_broadcast_endpoint_changes(16);
+    let _load_balanced_channel = builder.channel();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    resolver
+        .add_ip_without_server("late".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+
+    assert_eq!(
+        next_added_endpoints(&mut changes, 1).await,
+        vec!["127.0.0.1:5000".parse::<SocketAddr>().unwrap()]
+    );
+}
+
+#[tokio::test]
+async fn endpoints_are_removed_when_their_records_expire() {
+    // Scenario:
+    // The probe interval is long, but the DNS records of the endpoints expire quickly.
+    // We want an endpoint that is gone from DNS to be removed once its record expires.
+    let mut resolver = ExpiringDnsResolver {
+        inner: TestDnsResolver::default(),
+        ttl: tokio::time::Duration::from_millis(200),
+    };
+    resolver
+        .inner
+        .add_ip_without_server("server".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+
+    let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_secs(600))
+        .with_endpoint_ttl_aware_removal(true)
+        .with_broadcast_endpoint_changes(16);
+    let _load_balanced_channel = builder.channel();
+
+    let address: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+    assert_eq!(next_added_endpoints(&mut changes, 1).await, vec![address]);
+
+    resolver
+        .inner
+        .remove_ip_and_not_server("server".to_string())
+        .await;
+
+    let change = tokio::time::timeout(tokio::time::Duration::from_secs(5), changes.recv())
+        .await
+        .expect("endpoint was not removed")
+        .unwrap();
+    assert_eq!(change, EndpointChangeEvent::Removed(address));
+}
+
+static ADDED_ENDPOINTS: std::sync::Mutex<Vec<SocketAddr>> = std::sync::Mutex::new(Vec::new());
+static REMOVED_ENDPOINTS: std::sync::Mutex<Vec<SocketAddr>> = std::sync::Mutex::new(Vec::new());
+
+/// Wait until `endpoints` holds `count` entries.
+async fn wait_for_hooks(endpoints: &std::sync::Mutex<Vec<SocketAddr>>, count: usize) {
+    tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
+        while endpoints.lock().unwrap().len() < count {
+            tokio::time::sleep(tokio::time::Duration::from_millis(3)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for endpoint hooks");
+}
+
+#[tokio::test]
+async fn endpoint_hooks_are_called_once_per_endpoint() {
+    // Scenario:
+    // Two endpoints are discovered and one of them disappears later.
+    // We want each hook to be called once for every endpoint it concerns.
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("a".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+    resolver
+        .add_ip_without_server("b".to_string(), "127.0.0.2:5000".to_string())
+        .await;
+
+    let _load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .on_endpoint_added(|addr| ADDED_ENDPOINTS.lock().unwrap().push(addr))
+        .on_endpoint_removed(|addr| REMOVED_ENDPOINTS.lock().unwrap().push(addr))
+        .channel();
+
+    wait_for_hooks(&ADDED_ENDPOINTS, 2).await;
+    resolver.remove_ip_and_not_server("a".to_string()).await;
+    wait_for_hooks(&REMOVED_ENDPOINTS, 1).await;
+
+    // Give the probe a few more cycles to report anything twice.
+    tokio::time::sleep(tokio::time::Duration::from_millis(30)).await;
+
+    let mut added = ADDED_ENDPOINTS.lock().unwrap().clone();
+    added.sort();
+    assert_eq!(
+        added,
+        vec![
+            "127.0.0.1:5000".parse::<SocketAddr>().unwrap(),
+            "127.0.0.2:5000".parse().unwrap()
+        ]
+    );
+    assert_eq!(
+        *REMOVED_ENDPOINTS.lock().unwrap(),
+        vec!["127.0.0.1:5000".parse::<SocketAddr>().unwrap()]
+    );
+}
+
+#[tokio::test]
+async fn channel_ready_callback_is_called_once() {
+    // Scenario:
+    // The service has no endpoints at first, then gains one, loses it and gains another.
+    // We want the callback to be called once, when the first endpoint is added.
+    let mut resolver = TestDnsResolver::default();
+    let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let callback_calls = Arc::clone(&calls);
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .with_channel_ready_callback(move || {
+            callback_calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        })
+        .channel();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    resolver
+        .add_ip_without_server("a".to_string(), "127.0.0.1:5000".to_string())
+        .await;
+    wait_for_peer_count(&load_balanced_channel, 1).await;
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    resolver.remove_ip_and_not_server("a".to_string()).await;
+    wait_for_peer_count(&load_balanced_channel, 0).await;
+    resolver
+        .add_ip_without_server("b".to_string(), "127.0.0.2:5000".to_string())
+        .await;
+    wait_for_peer_count(&load_balanced_channel, 1).await;
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+/// The server takes a long time to answer the first request only.
+/// Check that a hedged copy of the request is answered well before that.
+async fn assert_slow_requests_are_hedged(max_hedged_requests: usize) {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(2);
+    let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl(
+            "server".to_string(),
+            SlowFirstCallTesterImpl {
+                inner: TesterImpl {
+                    sender: Arc::new(Mutex::new(sender)),
+                    name: "server".to_string(),
+                },
+                calls: Arc::clone(&calls),
+                delay: tokio::time::Duration::from_secs(30),
+            },
+        )
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .with_hedging(tokio::time::Duration::from_millis(50), max_hedged_requests)
+        .channel();
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    let res = tokio::time::timeout(
+        tokio::time::Duration::from_secs(5),
+        client.test(tonic::Request::new(Ping {})),
+    )
+    .await
+    .expect("the hedged request was not answered")
+    .expect("failed to call server");
+
+    assert_eq!(
+        "server",
+        get_payload_raw(res.into_inner().payload.expect("no payload"))
+    );
+    assert_eq!(receiver.recv().await.as_deref(), Some("server"));
+    assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn slow_requests_are_hedged() {
+    assert_slow_requests_are_hedged(1).await;
+}
+
+#[tokio::test]
+async fn unbounded_hedging_only_copies_requests_as_they_are_sent() {
+    assert_slow_requests_are_hedged(usize::MAX).await;
+}
+
+#[tokio::test]
+async fn additional_services_are_resolved_concurrently() {
+    // Scenario:
+    // A probe resolves five services whose lookups are slow.
+    // We want a probe cycle to take about as long as a single lookup.
+    let delay = tokio::time::Duration::from_millis(200);
+    let slow_resolver = || SlowDnsResolver {
+        inner: TestDnsResolver::default(),
+        delay,
+    };
+
+    let mut single = detached_probe(slow_resolver());
+    let start = tokio::time::Instant::now();
+    single.probe_once().await.expect("probe cycle failed");
+    let single_lookup = start.elapsed();
+
+    let mut multiple = (1..5).fold(detached_probe(slow_resolver()), |probe, i| {
+        probe.with_additional_service(("test", 5000 + i).into())
+    });
+    let start = tokio::time::Instant::now();
+    multiple.probe_once().await.expect("probe cycle failed");
+    let parallel_lookups = start.elapsed();
+
+    assert!(
+        parallel_lookups < 2 * single_lookup,
+        "5 lookups took {:?}, a single one {:?}",
+        parallel_lookups,
+        single_lookup
+    );
+}
+
+#[tokio::test]
+async fn endpoints_are_only_added_once_they_accept_connections() {
+    // Scenario:
+    // One endpoint accepts connections, the other only starts listening later.
+    // We want the second endpoint to be added once it listens, and not before.
+    let listening = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("failed to bind listener");
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("failed to bind listener");
+    let listening_address = listening.local_addr().unwrap();
+    let closed_address = closed.local_addr().unwrap();
+    drop(closed);
+
+    let mut resolver = TestDnsResolver::default();
+    for address in &[listening_address, closed_address] {
+        resolver
+            .add_ip_without_server(address.to_string(), address.to_string())
+            .await;
+    }
+
+    let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .with_tcp_probe(tokio::time::Duration::from_millis(500))
+        .with_broadcast_endpoint_changes(16);
+    let _load_balanced_channel = builder.channel();
+
+    assert_eq!(
+        next_added_endpoints(&mut changes, 1).await,
+        vec![listening_address]
+    );
+
+    let _reopened = tokio::net::TcpListener::bind(closed_address)
+        .await
+        .expect("failed to bind listener");
+    assert_eq!(
+        next_added_endpoints(&mut changes, 1).await,
+        vec![closed_address]
+    );
+}
+
+/// Connections accepted by [`track_open_connections`].
+#[derive(Default)]
+struct AcceptedConnections {
+    open: Vec<std::net::TcpStream>,
+    accepted: usize,
+    max_open: usize,
+}
+
+/// Accept connections on `listeners` and keep track of how many were open at the same time.
+///
+/// A connection counts as open until the peer closes it, which the probe does right after connecting.
+fn track_open_connections(
+    listeners: Vec<tokio::net::TcpListener>,
+) -> Arc<std::sync::Mutex<AcceptedConnections>> {
+    let connections = Arc::new(std::sync::Mutex::new(AcceptedConnections::default()));
+    for listener in listeners {
+        let connections = Arc::clone(&connections);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let stream = stream.into_std().expect("failed to convert stream");
+                let mut connections = connections.lock().unwrap();
+                connections.accepted += 1;
+                connections.open.push(stream);
+                // `peek` returns 0 bytes once the peer has closed the connection.
+                connections
+                    .open
+                    .retain(|stream| !matches!(stream.peek(&mut [0]), Ok(0)));
+                connections.max_open = connections.max_open.max(connections.open.len());
+            }
+        });
+    }
+    connections
+}
+
+// Accepting on other worker threads observes connections the probe has not closed yet.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn endpoints_are_probed_one_by_one_with_a_backlog_of_one() {
+    let mut listeners = Vec::new();
+    let mut resolver = TestDnsResolver::default();
+    for _ in 0..3 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind listener");
+        let address = listener.local_addr().unwrap();
+        resolver
+            .add_ip_without_server(address.to_string(), address.to_string())
+            .await;
+        listeners.push(listener);
+    }
+    let mut addresses: Vec<SocketAddr> = listeners
+        .iter()
+        .map(|listener| listener.local_addr().unwrap())
+        .collect();
+    addresses.sort();
+    let connections = track_open_connections(listeners);
+
+    let (builder, mut changes) = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .with_tcp_probe(tokio::time::Duration::from_millis(500))
+        .with_connection_backlog(NonZeroUsize::new(1).unwrap())
+        .with_endpoint_sort_strategy(EndpointSortStrategy::ByIpAddress)
+        .with_broadcast_endpoint_changes(16);
+    let _load_balanced_channel = builder.channel();
+
+    assert_eq!(next_added_endpoints(&mut changes, 3).await, addresses);
+
+    // Every endpoint has been connected to at least once, wait until all of them are accepted.
+    tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
+        while connections.lock().unwrap().accepted < 3 {
+            tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for the probe connections");
+    assert!(connections.lock().unwrap().max_open <= 1);
+}
+
+#[tokio::test]
+async fn endpoint_labels_are_looked_up_by_ip_address() {
+    let mut labels = std::collections::HashMap::new();
+    labels.insert(
+        "192.168.1.1".parse().unwrap(),
+        vec![("zone".to_string(), "us-east-1a".to_string())]
+            .into_iter()
+            .collect(),
+    );
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(TestDnsResolver::default())
+        .with_endpoint_metadata_labels(labels)
+        .channel();
+
+    let labelled: SocketAddr = "192.168.1.1:5000".parse().unwrap();
+    let unlabelled: SocketAddr = "192.168.1.2:5000".parse().unwrap();
+    assert_eq!(
+        load_balanced_channel.endpoint_label(labelled, "zone"),
+        Some("us-east-1a")
+    );
+    assert_eq!(load_balanced_channel.endpoint_label(labelled, "rack"), None);
+    assert_eq!(
+        load_balanced_channel.endpoint_label(unlabelled, "zone"),
+        None
+    );
+}
+
+#[tokio::test]
+async fn custom_alpn_protocols_are_offered_next_to_h2() {
+    // Scenario:
+    // The rustls config offers no ALPN protocol and a custom one is configured.
+    // We want the handshake to still negotiate `h2`, which tonic requires.
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let test_certificate = TestSslCertificate::generate();
+    let identity = tonic::transport::Identity::from_pem(
+        test_certificate.pem_certificate(),
+        test_certificate.pem_private_key(),
+    );
+    let mut resolver = TestDnsResolver::new_with_tls(ServerTlsConfig::new().identity(identity));
+    resolver
+        .add_server_with_provided_impl(
+            "server".to_string(),
+            TesterImpl {
+                sender: Arc::new(Mutex::new(sender)),
+                name: "server".to_string(),
+            },
+        )
+        .await;
+
+    let mut rustls_client_config = rustls::ClientConfig::new();
+    rustls_client_config
+        .dangerous()
+        .set_certificate_verifier(std::sync::Arc::new(NoVerifier {}));
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .with_rustls_tls(rustls_client_config)
+        .with_alpn_protocols(vec![b"istio".to_vec()])
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .channel();
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    let res = client
+        .test(tonic::Request::new(Ping {}))
+        .await
+        .expect("failed to call server");
+
+    assert_eq!(
+        "server",
+        get_payload_raw(res.into_inner().payload.expect("no payload"))
+    );
+    assert_eq!(receiver.recv().await.as_deref(), Some("server"));
+}
+
+#[tokio::test]
+async fn connection_strings_verify_tls_against_their_own_host() {
+    // Scenario:
+    // tls is configured for the service `test` before a connection string replaces it with
+    // `localhost`. We want the server certificate to be verified against `localhost`.
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let test_certificate = TestSslCertificate::generate();
+    let identity = tonic::transport::Identity::from_pem(
+        test_certificate.pem_certificate(),
+        test_certificate.pem_private_key(),
+    );
+    let mut resolver = TestDnsResolver::new_with_tls(ServerTlsConfig::new().identity(identity));
+    resolver
+        .add_server_with_provided_impl(
+            "server".to_string(),
+            TesterImpl {
+                sender: Arc::new(Mutex::new(sender)),
+                name: "server".to_string(),
+            },
+        )
+        .await;
+
+    let verifier = Arc::new(RecordingVerifier::default());
+    let mut rustls_client_config = rustls::ClientConfig::new();
+    rustls_client_config.set_protocols(&[b"h2".to_vec()]);
+    rustls_client_config
+        .dangerous()
+        .set_certificate_verifier(verifier.clone());
+    let tls_config = ClientTlsConfig::new().rustls_client_config(rustls_client_config);
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .with_tls(tls_config)
+        .with_connection_string("grpcs://localhost:5000")
+        .expect("connection string was rejected")
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .channel();
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    let res = client
+        .test(tonic::Request::new(Ping {}))
+        .await
+        .expect("failed to call server");
+
+    assert_eq!(
+        "server",
+        get_payload_raw(res.into_inner().payload.expect("no payload"))
+    );
+    assert_eq!(receiver.recv().await.as_deref(), Some("server"));
+    assert_eq!(*verifier.dns_names.lock().unwrap(), vec!["localhost"]);
+}
+
+#[tokio::test]
+async fn unary_methods_can_be_wrapped_in_tower_middleware() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl(
+            "server".to_string(),
+            TesterImpl {
+                sender: Arc::new(Mutex::new(sender)),
+                name: "server".to_string(),
+            },
+        )
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(tokio::time::Duration::from_millis(3))
+        .channel();
+    let test = load_balanced_channel.unary_service::<Ping, Pong>("/test.Tester/Test");
+    let mut test = tower::timeout::Timeout::new(test, tokio::time::Duration::from_secs(5));
+
+    let res = test
+        .ready()
+        .await
+        .expect("service is not ready")
+        .call(tonic::Request::new(Ping {}))
+        .await
+        .expect("failed to call server");
+
+    assert_eq!(
+        "server",
+        get_payload_raw(res.into_inner().payload.expect("no payload"))
+    );
+    assert_eq!(receiver.recv().await.as_deref(), Some("server"));
+}
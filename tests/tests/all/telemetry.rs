@@ -0,0 +1,99 @@
+use crate::lookup::{CountingDnsResolver, FailingDnsResolver};
+use ginepro::LoadBalancedChannelBuilder;
+use opentelemetry::global::BoxedTracer;
+use opentelemetry::trace::{Status, TracerProvider};
+use opentelemetry::{Key, Value};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+
+fn tracer() -> (BoxedTracer, InMemorySpanExporter, SdkTracerProvider) {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let tracer = BoxedTracer::new(Box::new(provider.tracer("ginepro-tests")));
+
+    (tracer, exporter, provider)
+}
+
+fn attribute(span: &SpanData, key: &'static str) -> Option<Value> {
+    span.attributes
+        .iter()
+        .find(|attribute| attribute.key == Key::from_static_str(key))
+        .map(|attribute| attribute.value.clone())
+}
+
+#[tokio::test]
+async fn every_probe_cycle_is_traced() {
+    // Scenario:
+    // The probe resolves a single endpoint several times.
+    // We want one ended span per probe cycle carrying the endpoint count.
+    let mut resolver = CountingDnsResolver::default();
+    resolver
+        .inner
+        .add_ip_without_server("server".into(), "127.0.0.1:5000".into())
+        .await;
+    let (tracer, exporter, _provider) = tracer();
+    let probe_interval = tokio::time::Duration::from_millis(10);
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(probe_interval)
+        .with_tracer(tracer)
+        .channel();
+
+    tokio::time::sleep(probe_interval * 5).await;
+    let spans = exporter
+        .get_finished_spans()
+        .expect("failed to read finished spans");
+    let lookups = resolver.lookups();
+    drop(load_balanced_channel);
+
+    // The last lookup may belong to a cycle that has not ended yet.
+    assert!(
+        spans.len() == lookups || spans.len() + 1 == lookups,
+        "{} spans for {} lookups",
+        spans.len(),
+        lookups
+    );
+    assert!(spans.len() > 1);
+    for span in spans {
+        assert_eq!(span.name, "ginepro.probe");
+        assert_eq!(attribute(&span, "net.peer.name"), Some("test".into()));
+        assert_eq!(attribute(&span, "net.peer.port"), Some(5000_i64.into()));
+        assert_eq!(attribute(&span, "endpoint.count"), Some(1_i64.into()));
+        assert_eq!(attribute(&span, "error"), None);
+        assert_eq!(span.status, Status::Unset);
+    }
+}
+
+#[tokio::test]
+async fn failed_probe_cycles_are_traced_as_errors() {
+    // Scenario:
+    // Every lookup of the probe fails.
+    // We want every span to carry the error.
+    let (tracer, exporter, _provider) = tracer();
+    let probe_interval = tokio::time::Duration::from_millis(10);
+
+    let _load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .await
+        .expect("failed to init")
+        .lookup_service(FailingDnsResolver)
+        .dns_probe_interval(probe_interval)
+        .with_tracer(tracer)
+        .channel();
+
+    tokio::time::sleep(probe_interval * 5).await;
+    let spans = exporter
+        .get_finished_spans()
+        .expect("failed to read finished spans");
+
+    assert!(!spans.is_empty());
+    for span in spans {
+        assert_eq!(span.name, "ginepro.probe");
+        assert_eq!(attribute(&span, "endpoint.count"), None);
+        assert_eq!(attribute(&span, "error"), Some("lookup failed".into()));
+        assert_eq!(span.status, Status::error("lookup failed"));
+    }
+}
@@ -0,0 +1,41 @@
+use ginepro::ServiceDefinition;
+use std::convert::TryFrom;
+use url::Url;
+
+#[test]
+fn service_definitions_are_read_from_urls() {
+    let definition = ServiceDefinition::try_from(Url::parse("grpc://my.service.uri:5000").unwrap())
+        .expect("failed to read url");
+    assert_eq!(definition.hostname, "my.service.uri");
+    assert_eq!(definition.port, 5000);
+
+    let definition = ServiceDefinition::from_url(&Url::parse("grpcs://[::1]/my.Service").unwrap())
+        .expect("failed to read url");
+    assert_eq!(definition.hostname, "::1");
+    assert_eq!(definition.port, 443);
+}
+
+#[test]
+fn urls_without_a_known_port_are_rejected() {
+    let url = Url::parse("load-balanced-grpc://my.service.uri").unwrap();
+    assert!(ServiceDefinition::from_url(&url).is_err());
+}
+
+#[test]
+fn service_definitions_convert_to_grpc_urls() {
+    let url = Url::try_from(ServiceDefinition::loopback_v6(5000)).expect("invalid url");
+    assert_eq!(url.as_str(), "grpc://[::1]:5000");
+
+    let url =
+        Url::try_from(ServiceDefinition::from(("my.service.uri", 5000))).expect("invalid url");
+    assert_eq!(
+        ServiceDefinition::from_url(&url).unwrap().to_string(),
+        "my.service.uri:5000"
+    );
+}
+
+#[test]
+fn hostnames_that_are_not_url_hosts_are_rejected() {
+    let definition = ServiceDefinition::from(("my service", 5000));
+    assert!(Url::try_from(definition).is_err());
+}
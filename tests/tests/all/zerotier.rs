@@ -0,0 +1,73 @@
+use ginepro::{LookupService, ServiceDefinition, ZeroTierLookupService};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+static MEMBERS: &str = r#"[
+    {"name": "api", "config": {"ipAssignments": ["10.147.17.1", "fd00::1"]}},
+    {"name": "api", "config": {"ipAssignments": ["10.147.17.2"]}},
+    {"name": "db", "config": {"ipAssignments": ["10.147.17.3"]}}
+]"#;
+
+/// Serve [`MEMBERS`] for network `abc` to requests authenticated with `token`.
+fn start_api() -> SocketAddr {
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(service_fn(|request: Request<Body>| async move {
+            let response =
+                if request.headers().get("X-ZT1-Auth").map(|t| t.as_bytes()) != Some(b"token") {
+                    Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(Body::empty())
+                } else if request.uri().path() == "/network/abc/member" {
+                    Response::builder().body(Body::from(MEMBERS))
+                } else {
+                    Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(Body::empty())
+                };
+            Ok::<_, Infallible>(response.unwrap())
+        }))
+    });
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let address = server.local_addr();
+    tokio::spawn(server);
+    address
+}
+
+#[tokio::test]
+async fn members_are_resolved_by_name() {
+    let api_url = format!("http://{}", start_api());
+    let lookup =
+        ZeroTierLookupService::with_api_url(&api_url, "abc", "token").expect("invalid url");
+
+    let endpoints = lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("api", 5000)))
+        .await
+        .expect("lookup failed");
+
+    let expected: HashSet<SocketAddr> = ["10.147.17.1:5000", "[fd00::1]:5000", "10.147.17.2:5000"]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect();
+    assert_eq!(endpoints, expected);
+
+    let endpoints = lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("unknown", 5000)))
+        .await
+        .expect("lookup failed");
+    assert!(endpoints.is_empty());
+}
+
+#[tokio::test]
+async fn unauthorized_lookups_fail() {
+    let api_url = format!("http://{}", start_api());
+    let lookup =
+        ZeroTierLookupService::with_api_url(&api_url, "abc", "wrong token").expect("invalid url");
+
+    assert!(lookup
+        .resolve_service_endpoints(&ServiceDefinition::from(("api", 5000)))
+        .await
+        .is_err());
+}